@@ -0,0 +1,303 @@
+use std::f64::consts::*;
+
+use crate::color::*;
+use crate::light::*;
+use crate::rng::*;
+use crate::sample_pattern::*;
+use crate::sampling::*;
+use crate::types::*;
+
+/// How many shadow rays an area light casts toward each point unless told otherwise.
+pub const DEFAULT_LIGHT_SAMPLES: u32 = 16;
+
+/// A parallelogram of light spanned by `edge_u` and `edge_v` from `corner`. It shines
+/// from both sides, like a point light whose intensity is spread evenly over it.
+pub struct RectangleLight {
+    pub corner: Point3f,
+    pub edge_u: Vec3f,
+    pub edge_v: Vec3f,
+    pub color: Color,
+    /// Radiant intensity in watts per steradian of the whole light, scaling `color`.
+    pub intensity: Float,
+    pub attenuation: Attenuation,
+    /// The number of shadow rays, rounded up to a square number so they can be
+    /// stratified over a grid of cells on the light.
+    pub samples: u32,
+}
+
+impl RectangleLight {
+    /// The density of all of the samples, per unit solid angle, of directions toward
+    /// the point `distance` away along `direction`.
+    fn pdf(&self, direction: &Vec3f, distance: Float) -> Float {
+        let area_normal = self.edge_u.cross(&self.edge_v);
+        sample_count(self.samples) * distance * distance / direction.dot(&area_normal).abs()
+    }
+}
+
+impl Light for RectangleLight {
+    fn samples(&self, point: &Point3f, rng: &mut Rng) -> Vec<LightSample> {
+        let (positions, color) = stratify(self.samples, self.color * self.intensity, rng);
+        positions
+            .into_iter()
+            .map(|(u, v)| {
+                let offset = self.corner + self.edge_u * u + self.edge_v * v - point;
+                let distance = offset.norm();
+                let direction = offset.normalize();
+                LightSample {
+                    direction,
+                    distance,
+                    color: color * self.attenuation.factor(distance),
+                    pdf: self.pdf(&direction, distance),
+                }
+            })
+            .collect()
+    }
+
+    fn hit(&self, origin: &Point3f, direction: &Vec3f) -> Option<LightHit> {
+        let area_normal = self.edge_u.cross(&self.edge_v);
+        let denominator = direction.dot(&area_normal);
+        if denominator.abs() < EPSILON {
+            return None;
+        }
+        let distance = (self.corner - origin).dot(&area_normal) / denominator;
+        if distance <= 0.0 {
+            return None;
+        }
+        // The hit's coordinates along the edges, from the areas it spans with each.
+        let offset = origin + direction * distance - self.corner;
+        let area_squared = area_normal.norm_squared();
+        let u = offset.cross(&self.edge_v).dot(&area_normal) / area_squared;
+        let v = self.edge_u.cross(&offset).dot(&area_normal) / area_squared;
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        let pdf = self.pdf(direction, distance);
+        let color = self.color * (self.intensity / sample_count(self.samples));
+        Some(LightHit {
+            distance,
+            radiance: color * (self.attenuation.factor(distance) * pdf),
+            pdf,
+        })
+    }
+}
+
+/// Jittered positions in the unit square, along with the share of `color` each carries.
+fn stratify(samples: u32, color: Color, rng: &mut Rng) -> (Vec<(Float, Float)>, Color) {
+    let positions = SamplePattern::Jittered.generate(samples, rng);
+    (positions, color * (1.0 / sample_count(samples)))
+}
+
+/// How many shadow rays are actually cast when asking for `samples`.
+fn sample_count(samples: u32) -> Float {
+    Float::from(SamplePattern::Jittered.sample_count(samples))
+}
+
+/// A glowing ball, whose intensity is spread evenly over the part of it visible from
+/// the lit point.
+pub struct SphereLight {
+    pub center: Point3f,
+    pub radius: Float,
+    pub color: Color,
+    /// Radiant intensity in watts per steradian of the whole light, scaling `color`.
+    pub intensity: Float,
+    pub attenuation: Attenuation,
+    /// The number of shadow rays, rounded up to a square number like a
+    /// `RectangleLight`'s.
+    pub samples: u32,
+}
+
+impl SphereLight {
+    fn is_outside(&self, point: &Point3f) -> bool {
+        (self.center - point).norm_squared() > self.radius * self.radius
+    }
+
+    /// How far a ray from `point` travels in `direction` to meet the sphere: the near
+    /// side from outside of it and the far side from within.
+    fn distance(&self, point: &Point3f, direction: &Vec3f) -> Option<Float> {
+        let to_center = self.center - point;
+        let b = direction.dot(&to_center);
+        let discriminant = b * b - to_center.norm_squared() + self.radius * self.radius;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        if self.is_outside(point) {
+            Some(b - root).filter(|&d| d > 0.0)
+        } else {
+            Some(b + root)
+        }
+    }
+
+    /// The cosine of the angle between the axis toward the center and the edges of the
+    /// cone of directions from `point` that meet the sphere.
+    fn cos_max(&self, point: &Point3f) -> Float {
+        let distance_squared = (self.center - point).norm_squared();
+        (1.0 - self.radius * self.radius / distance_squared)
+            .max(0.0)
+            .sqrt()
+    }
+
+    /// The density of all of the samples taken from `point`, per unit solid angle.
+    fn pdf(&self, point: &Point3f) -> Float {
+        let solid_angle = if self.is_outside(point) {
+            2.0 * PI * (1.0 - self.cos_max(point))
+        } else {
+            4.0 * PI
+        };
+        sample_count(self.samples) / solid_angle
+    }
+}
+
+impl Light for SphereLight {
+    fn samples(&self, point: &Point3f, rng: &mut Rng) -> Vec<LightSample> {
+        let outside = self.is_outside(point);
+        let axis = (self.center - point).normalize();
+        let cos_max = self.cos_max(point);
+        let pdf = self.pdf(point);
+
+        let (positions, color) = stratify(self.samples, self.color * self.intensity, rng);
+        positions
+            .into_iter()
+            .map(|(u, v)| {
+                let direction = if outside {
+                    uniform_cone(u, v, &axis, cos_max)
+                } else {
+                    uniform_sphere(u, v)
+                };
+                // Directions on the very edge of the cone only graze the sphere.
+                let distance = self
+                    .distance(point, &direction)
+                    .unwrap_or_else(|| direction.dot(&(self.center - point)));
+                LightSample {
+                    direction,
+                    distance,
+                    color: color * self.attenuation.factor(distance),
+                    pdf,
+                }
+            })
+            .collect()
+    }
+
+    fn hit(&self, origin: &Point3f, direction: &Vec3f) -> Option<LightHit> {
+        let distance = self.distance(origin, direction)?;
+        let pdf = self.pdf(origin);
+        let color = self.color * (self.intensity / sample_count(self.samples));
+        Some(LightHit {
+            distance,
+            radiance: color * (self.attenuation.factor(distance) * pdf),
+            pdf,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+
+    #[test]
+    fn it_samples_rectangles_over_their_area() {
+        let light = RectangleLight {
+            corner: Point3::new(-1.0, 4.0, -1.0),
+            edge_u: Vector3::new(2.0, 0.0, 0.0),
+            edge_v: Vector3::new(0.0, 0.0, 2.0),
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+            samples: 5,
+        };
+        let samples = light.samples(&Point3::origin(), &mut Rng::new(0, 0));
+        assert_eq!(samples.len(), 9);
+        let total = samples
+            .iter()
+            .fold(Color::new(0.0, 0.0, 0.0), |total, s| total + s.color);
+        assert_relative_eq!(total.0, Vector3::new(1.0, 1.0, 1.0), epsilon = 1e-12);
+        for s in &samples {
+            let hit = Point3::origin() + s.direction * s.distance;
+            assert_relative_eq!(hit.y, 4.0, epsilon = 1e-12);
+            assert!(hit.x.abs() <= 1.0 && hit.z.abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn it_samples_the_visible_side_of_spheres() {
+        let light = SphereLight {
+            center: Point3::new(0.0, 0.0, 5.0),
+            radius: 1.0,
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+            samples: 16,
+        };
+        let samples = light.samples(&Point3::origin(), &mut Rng::new(0, 0));
+        assert_eq!(samples.len(), 16);
+        for s in &samples {
+            let hit = Point3::origin() + s.direction * s.distance;
+            assert_relative_eq!((hit - light.center).norm(), 1.0, epsilon = 1e-9);
+            // The hit faces the lit point.
+            assert!((hit - light.center).z < 0.0);
+        }
+    }
+
+    fn assert_hits_match_samples(light: &Light, point: &Point3f) {
+        let samples = light.samples(point, &mut Rng::new(0, 0));
+        for s in &samples {
+            let hit = light.hit(point, &s.direction).unwrap();
+            assert_relative_eq!(hit.distance, s.distance, epsilon = 1e-9);
+            assert_relative_eq!(hit.pdf, s.pdf, epsilon = 1e-9);
+            assert_relative_eq!(
+                (hit.radiance * (1.0 / hit.pdf)).0,
+                s.color.0,
+                epsilon = 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn it_is_hit_where_it_is_sampled() {
+        let rectangle = RectangleLight {
+            corner: Point3::new(-1.0, 4.0, -2.0),
+            edge_u: Vector3::new(2.0, 1.0, 0.0),
+            edge_v: Vector3::new(0.0, 0.0, 3.0),
+            color: Color::new(1.0, 0.5, 0.25),
+            intensity: 3.0,
+            attenuation: Attenuation::INVERSE_SQUARE,
+            samples: 9,
+        };
+        assert_hits_match_samples(&rectangle, &Point3::new(0.5, 0.0, 0.0));
+        assert!(rectangle
+            .hit(&Point3::origin(), &Vector3::new(1.0, 1.0, 0.0).normalize())
+            .is_none());
+
+        let sphere = SphereLight {
+            center: Point3::new(0.0, 0.0, 5.0),
+            radius: 1.0,
+            color: Color::new(1.0, 0.5, 0.25),
+            intensity: 3.0,
+            attenuation: Attenuation::INVERSE_SQUARE,
+            samples: 9,
+        };
+        assert_hits_match_samples(&sphere, &Point3::origin());
+        assert_hits_match_samples(&sphere, &Point3::new(0.0, 0.5, 5.0));
+        assert!(sphere.hit(&Point3::origin(), &-Vector3::z()).is_none());
+    }
+
+    #[test]
+    fn it_samples_spheres_from_inside() {
+        let light = SphereLight {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 2.0,
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+            samples: 4,
+        };
+        let point = Point3::new(0.5, 0.0, 0.0);
+        for s in light.samples(&point, &mut Rng::new(0, 0)) {
+            let hit = point + s.direction * s.distance;
+            assert_relative_eq!((hit - light.center).norm(), 2.0, epsilon = 1e-9);
+        }
+    }
+}
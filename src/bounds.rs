@@ -0,0 +1,225 @@
+use na::*;
+
+use crate::ray::*;
+use crate::types::*;
+
+/// An axis-aligned bounding box. Unbounded shapes such as planes have infinite extents.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min: Point3f,
+    pub max: Point3f,
+}
+
+impl BoundingBox {
+    /// A box containing nothing, which is the identity for `union`.
+    pub fn empty() -> Self {
+        Self {
+            min: Point3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            max: Point3::new(-Float::INFINITY, -Float::INFINITY, -Float::INFINITY),
+        }
+    }
+
+    pub fn infinite() -> Self {
+        Self {
+            min: Point3::new(-Float::INFINITY, -Float::INFINITY, -Float::INFINITY),
+            max: Point3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        }
+    }
+
+    pub fn from_points(points: &[Point3f]) -> Self {
+        points
+            .iter()
+            .fold(Self::empty(), |bounds, p| bounds.include(p))
+    }
+
+    pub fn include(&self, p: &Point3f) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x.min(p.x),
+                self.min.y.min(p.y),
+                self.min.z.min(p.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(p.x),
+                self.max.y.max(p.y),
+                self.max.z.max(p.z),
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn centroid(&self) -> Point3f {
+        Point3::from((self.min.coords + self.max.coords) / 2.0)
+    }
+
+    pub fn surface_area(&self) -> Float {
+        let d = self.max - self.min;
+        if d.x < 0.0 || d.y < 0.0 || d.z < 0.0 {
+            return 0.0;
+        }
+
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Returns the axis along which the box is longest.
+    pub fn longest_axis(&self) -> usize {
+        let d = self.max - self.min;
+        if d.x >= d.y && d.x >= d.z {
+            0
+        } else if d.y >= d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Bounds this box after being transformed, by transforming each of its corners.
+    pub fn transform(&self, m: &Projective3<Float>) -> Self {
+        if !self.is_finite() {
+            return Self::infinite();
+        }
+
+        let mut bounds = Self::empty();
+        for &x in &[self.min.x, self.max.x] {
+            for &y in &[self.min.y, self.max.y] {
+                for &z in &[self.min.z, self.max.z] {
+                    bounds = bounds.include(&(m * Point3::new(x, y, z)));
+                }
+            }
+        }
+        bounds
+    }
+
+    /// Slab test against the box. `inverse_direction` is the component-wise reciprocal of
+    /// the ray direction, which callers testing many boxes should compute only once.
+    /// Returns the distance at which the ray enters the box, if it does so before `t_max`.
+    pub fn intersection(
+        &self,
+        ray: &Ray,
+        inverse_direction: &Vec3f,
+        t_max: Float,
+    ) -> Option<Float> {
+        let mut near = 0.0 as Float;
+        let mut far = t_max;
+        for axis in 0..3 {
+            let t1 = (self.min[axis] - ray.origin[axis]) * inverse_direction[axis];
+            let t2 = (self.max[axis] - ray.origin[axis]) * inverse_direction[axis];
+            // `max` and `min` ignore NaNs, which arise when the ray lies in a slab's plane.
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+
+        if near > far {
+            None
+        } else {
+            Some(near)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    fn inverse(v: &Vec3f) -> Vec3f {
+        Vector3::new(1.0 / v.x, 1.0 / v.y, 1.0 / v.z)
+    }
+
+    #[test]
+    fn it_unions_boxes() {
+        let a = BoundingBox::from_points(&[Point3::new(-5.0, -2.0, 0.0)]);
+        let b = BoundingBox::from_points(&[Point3::new(7.0, 4.0, 4.0)]);
+        let u = a.union(&b).union(&BoundingBox::empty());
+        assert_relative_eq!(u.min, Point3::new(-5.0, -2.0, 0.0));
+        assert_relative_eq!(u.max, Point3::new(7.0, 4.0, 4.0));
+        assert_relative_eq!(u.centroid(), Point3::new(1.0, 1.0, 2.0));
+        assert_eq!(u.longest_axis(), 0);
+    }
+
+    #[test]
+    fn it_computes_surface_area() {
+        assert_relative_eq!(unit_box().surface_area(), 24.0);
+        assert_relative_eq!(BoundingBox::empty().surface_area(), 0.0);
+    }
+
+    #[test]
+    fn it_transforms_box() {
+        let m = Rotation3::from_axis_angle(&Vector3::y_axis(), FRAC_PI_4) * Projective3::identity();
+        let b = unit_box().transform(&m);
+        let k = (2.0 as Float).sqrt();
+        assert_relative_eq!(b.min, Point3::new(-k, -1.0, -k));
+        assert_relative_eq!(b.max, Point3::new(k, 1.0, k));
+    }
+
+    #[test]
+    fn it_keeps_infinite_box_infinite_under_transform() {
+        let m = Rotation3::from_axis_angle(&Vector3::y_axis(), FRAC_PI_4) * Projective3::identity();
+        assert!(!BoundingBox::infinite().transform(&m).is_finite());
+    }
+
+    #[test]
+    fn it_intersects_box() {
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let r = Ray {
+            origin: Point3::new(0.5, 0.5, -5.0),
+            direction,
+        };
+        let t = unit_box().intersection(&r, &inverse(&direction), Float::INFINITY);
+        assert_relative_eq!(t.unwrap(), 4.0);
+        assert!(unit_box()
+            .intersection(&r, &inverse(&direction), 3.0)
+            .is_none());
+    }
+
+    #[test]
+    fn it_misses_box() {
+        let direction = Vector3::new(0.0, 0.0, 1.0);
+        let r = Ray {
+            origin: Point3::new(2.0, 0.5, -5.0),
+            direction,
+        };
+        assert!(unit_box()
+            .intersection(&r, &inverse(&direction), Float::INFINITY)
+            .is_none());
+    }
+
+    #[test]
+    fn it_intersects_box_from_inside() {
+        let direction = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction,
+        };
+        let t = unit_box().intersection(&r, &inverse(&direction), Float::INFINITY);
+        assert_relative_eq!(t.unwrap(), 0.0);
+    }
+}
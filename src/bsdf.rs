@@ -0,0 +1,370 @@
+//! Scattering functions, which say how much of the light arriving at a surface from one
+//! direction leaves it in another, and how to pick directions to follow them.
+
+use std::f64::consts::*;
+
+use crate::color::*;
+use crate::ray::*;
+use crate::rng::*;
+use crate::sampling::*;
+use crate::types::*;
+
+/// A direction picked by `Bsdf::sample`.
+pub struct BsdfSample {
+    /// Unit vector away from the surface, toward where the light comes from.
+    pub direction: Vec3f,
+    /// The BSDF for `direction`, as `Bsdf::eval` would give it.
+    pub value: Color,
+    /// The probability density, per unit solid angle, of picking `direction`.
+    pub pdf: Float,
+}
+
+/// How a surface scatters light. `outgoing` and `incoming` are unit vectors pointing
+/// away from the surface, toward the viewer and toward the light respectively.
+pub trait Bsdf {
+    /// The radiance scattered toward `outgoing` per unit of irradiance arriving from
+    /// `incoming`.
+    fn eval(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Color;
+
+    /// Picks an incoming direction for light scattered toward `outgoing`, roughly in
+    /// proportion to how much of it the surface scatters. Returns None if the direction
+    /// picked can't contribute, such as one below the surface.
+    fn sample(&self, outgoing: &Vec3f, rng: &mut Rng) -> Option<BsdfSample>;
+
+    /// The density, per unit solid angle, with which `sample` picks `incoming`.
+    fn pdf(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Float;
+
+    /// Roughly the most light the surface reflects in any channel, for choosing between
+    /// it and perfect mirrors and glass. It must be positive wherever `eval` can be.
+    fn albedo(&self) -> Float;
+}
+
+/// A Lambertian surface with a glossy highlight, using the normalized form of the Phong
+/// lobe so that it never reflects more light than arrives. Together `diffuse` and
+/// `specular` should not exceed 1 in any channel.
+pub struct PhongBsdf {
+    /// Faces the side of the surface that `outgoing` is on.
+    pub normal: Vec3f,
+    /// The fraction of light scattered evenly in every direction.
+    pub diffuse: Color,
+    /// The fraction of light scattered around the mirror direction.
+    pub specular: Color,
+    /// How tightly the highlight is focused around the mirror direction.
+    pub exponent: Float,
+}
+
+impl PhongBsdf {
+    /// The chance of `sample` following the highlight rather than the diffuse part.
+    fn specular_chance(&self) -> Float {
+        let diffuse = self.diffuse.max_component().max(0.0);
+        let specular = self.specular.max_component().max(0.0);
+        if diffuse + specular <= 0.0 {
+            0.0
+        } else {
+            specular / (diffuse + specular)
+        }
+    }
+
+    fn mirror_direction(&self, outgoing: &Vec3f) -> Vec3f {
+        reflect(&-*outgoing, &self.normal)
+    }
+}
+
+impl Bsdf for PhongBsdf {
+    fn eval(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Color {
+        if outgoing.dot(&self.normal) <= 0.0 || incoming.dot(&self.normal) <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let cos_alpha = self.mirror_direction(outgoing).dot(incoming).max(0.0);
+        let highlight = (self.exponent + 2.0) / (2.0 * PI) * cos_alpha.powf(self.exponent);
+        self.diffuse * (1.0 / PI) + self.specular * highlight
+    }
+
+    fn sample(&self, outgoing: &Vec3f, rng: &mut Rng) -> Option<BsdfSample> {
+        let (u, v) = (rng.next_float(), rng.next_float());
+        let direction = if rng.next_float() < self.specular_chance() {
+            cosine_power_lobe(u, v, &self.mirror_direction(outgoing), self.exponent)
+        } else {
+            cosine_hemisphere(u, v, &self.normal)
+        };
+        if direction.dot(&self.normal) <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            value: self.eval(outgoing, &direction),
+            pdf: self.pdf(outgoing, &direction),
+        })
+    }
+
+    fn pdf(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Float {
+        let cos_theta = incoming.dot(&self.normal);
+        if cos_theta <= 0.0 {
+            return 0.0;
+        }
+        let cos_alpha = self.mirror_direction(outgoing).dot(incoming).max(0.0);
+        let chance = self.specular_chance();
+        (1.0 - chance) * cos_theta / PI
+            + chance * (self.exponent + 1.0) / (2.0 * PI) * cos_alpha.powf(self.exponent)
+    }
+
+    fn albedo(&self) -> Float {
+        (self.diffuse + self.specular).max_component().max(0.0)
+    }
+}
+
+/// The smallest GGX α used, since a perfectly smooth surface would need a distribution
+/// of microfacet normals infinitely dense in one direction.
+const MIN_ALPHA: Float = 1e-3;
+
+/// The least any surface reflects by the measure of `Bsdf::albedo`, since even black
+/// surfaces reflect light arriving at grazing angles.
+const MIN_ALBEDO: Float = 0.05;
+
+/// A physically based surface made of tiny mirror facets whose normals follow the GGX
+/// distribution, shadowing and masking each other as Smith's model predicts and
+/// reflecting as the Fresnel equations (in Schlick's approximation) say. Dielectrics
+/// add a Lambertian base underneath for the light the facets let through, while metals
+/// tint their reflections with the base color instead.
+pub struct GgxBsdf {
+    /// Faces the side of the surface that `outgoing` is on.
+    pub normal: Vec3f,
+    pub base_color: Color,
+    /// From 0 for a polished surface to 1 for a completely rough one. It is squared to
+    /// give GGX's α, which makes it look about evenly spaced.
+    pub roughness: Float,
+    /// From 0 for a dielectric to 1 for a metal.
+    pub metallic: Float,
+    /// The index of refraction, which decides how much dielectrics reflect head on.
+    pub ior: Float,
+}
+
+impl GgxBsdf {
+    fn alpha(&self) -> Float {
+        (self.roughness * self.roughness).max(MIN_ALPHA)
+    }
+
+    /// The reflectance head on.
+    fn f0(&self) -> Color {
+        let dielectric = ((self.ior - 1.0) / (self.ior + 1.0)).powi(2);
+        Color::new(dielectric, dielectric, dielectric) * (1.0 - self.metallic)
+            + self.base_color * self.metallic
+    }
+
+    /// The Schlick approximation of the reflectance of a facet seen at an angle whose
+    /// cosine is `cos_d`.
+    fn fresnel(&self, cos_d: Float) -> Color {
+        let f0 = self.f0();
+        f0 + (Color::new(1.0, 1.0, 1.0) - f0) * (1.0 - cos_d).max(0.0).powi(5)
+    }
+
+    /// The density of facet normals at an angle whose cosine is `cos_h` from the normal,
+    /// per unit of projected area.
+    fn distribution(&self, cos_h: Float) -> Float {
+        let alpha_squared = self.alpha() * self.alpha();
+        let d = cos_h * cos_h * (alpha_squared - 1.0) + 1.0;
+        alpha_squared / (PI * d * d)
+    }
+
+    /// The fraction of facets seen from a direction whose cosine with the normal is
+    /// `cos`, as Smith's model has it.
+    fn masking(&self, cos: Float) -> Float {
+        let alpha_squared = self.alpha() * self.alpha();
+        2.0 * cos / (cos + (alpha_squared + (1.0 - alpha_squared) * cos * cos).sqrt())
+    }
+
+    /// The chance of `sample` following the facets rather than the diffuse base.
+    fn specular_chance(&self) -> Float {
+        Float::midpoint(1.0, self.metallic)
+    }
+
+    fn half_vector(outgoing: &Vec3f, incoming: &Vec3f) -> Vec3f {
+        (outgoing + incoming).normalize()
+    }
+}
+
+impl Bsdf for GgxBsdf {
+    fn eval(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Color {
+        let (cos_o, cos_i) = (outgoing.dot(&self.normal), incoming.dot(&self.normal));
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let half = Self::half_vector(outgoing, incoming);
+        let fresnel = self.fresnel(outgoing.dot(&half));
+        let specular =
+            self.distribution(half.dot(&self.normal)) * self.masking(cos_o) * self.masking(cos_i)
+                / (4.0 * cos_o * cos_i);
+        // The base only receives the light the facets don't reflect.
+        let transmitted = Color::new(1.0, 1.0, 1.0) - fresnel;
+        let diffuse = self.base_color.mix(transmitted) * ((1.0 - self.metallic) / PI);
+        fresnel * specular + diffuse
+    }
+
+    fn sample(&self, outgoing: &Vec3f, rng: &mut Rng) -> Option<BsdfSample> {
+        let (u, v) = (rng.next_float(), rng.next_float());
+        let direction = if rng.next_float() < self.specular_chance() {
+            // Pick a facet normal in proportion to its projected area and reflect off it.
+            let alpha_squared = self.alpha() * self.alpha();
+            let cos_h = ((1.0 - u) / (1.0 + (alpha_squared - 1.0) * u)).sqrt();
+            let sin_h = (1.0 - cos_h * cos_h).max(0.0).sqrt();
+            let phi = 2.0 * PI * v;
+            let (tangent, bitangent) = orthonormal_basis(&self.normal);
+            let half = tangent * (sin_h * phi.cos())
+                + bitangent * (sin_h * phi.sin())
+                + self.normal * cos_h;
+            reflect(&-*outgoing, &half)
+        } else {
+            cosine_hemisphere(u, v, &self.normal)
+        };
+        if direction.dot(&self.normal) <= 0.0 {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            value: self.eval(outgoing, &direction),
+            pdf: self.pdf(outgoing, &direction),
+        })
+    }
+
+    fn pdf(&self, outgoing: &Vec3f, incoming: &Vec3f) -> Float {
+        let cos_i = incoming.dot(&self.normal);
+        if cos_i <= 0.0 {
+            return 0.0;
+        }
+        let half = Self::half_vector(outgoing, incoming);
+        let cos_h = half.dot(&self.normal).max(0.0);
+        // Reflecting off a facet squeezes the density of its normals by 4 cos θ.
+        let specular = self.distribution(cos_h) * cos_h / (4.0 * outgoing.dot(&half).abs());
+        let chance = self.specular_chance();
+        chance * specular + (1.0 - chance) * cos_i / PI
+    }
+
+    fn albedo(&self) -> Float {
+        let diffuse = self.base_color * (1.0 - self.metallic);
+        (diffuse + self.f0()).max_component().clamp(MIN_ALBEDO, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+
+    fn glossy() -> PhongBsdf {
+        PhongBsdf {
+            normal: Vector3::y(),
+            diffuse: Color::new(0.5, 0.25, 0.0),
+            specular: Color::new(0.5, 0.5, 0.5),
+            exponent: 20.0,
+        }
+    }
+
+    #[test]
+    fn it_samples_with_the_density_it_reports() {
+        let bsdf = glossy();
+        let outgoing = Vector3::new(1.0, 2.0, 0.5).normalize();
+        let mut rng = Rng::new(0, 0);
+        for _ in 0..100 {
+            if let Some(sample) = bsdf.sample(&outgoing, &mut rng) {
+                assert_relative_eq!(sample.direction.norm(), 1.0, epsilon = 1e-12);
+                assert_relative_eq!(sample.pdf, bsdf.pdf(&outgoing, &sample.direction));
+                assert_eq!(sample.value, bsdf.eval(&outgoing, &sample.direction));
+                assert!(sample.pdf > 0.0);
+            }
+        }
+        assert_relative_eq!(bsdf.pdf(&outgoing, &-Vector3::y()), 0.0);
+    }
+
+    #[test]
+    fn it_reflects_no_more_light_than_arrives() {
+        // Averaging f cos θ / pdf over samples estimates the reflected fraction.
+        let bsdf = PhongBsdf {
+            diffuse: Color::new(0.5, 0.5, 0.5),
+            ..glossy()
+        };
+        let outgoing = Vector3::y();
+        let mut rng = Rng::new(0, 0);
+        let mut reflected = 0.0;
+        for _ in 0..4000 {
+            if let Some(sample) = bsdf.sample(&outgoing, &mut rng) {
+                let cosine = sample.direction.dot(&bsdf.normal);
+                reflected += sample.value.0.x * cosine / sample.pdf / 4000.0;
+            }
+        }
+        // Seen head on, the highlight reflects all of the light it receives.
+        assert_relative_eq!(reflected, 1.0, epsilon = 0.05);
+    }
+
+    fn ggx(roughness: Float, metallic: Float) -> GgxBsdf {
+        GgxBsdf {
+            normal: Vector3::y(),
+            base_color: Color::new(1.0, 1.0, 1.0),
+            roughness,
+            metallic,
+            ior: 1.5,
+        }
+    }
+
+    /// Estimates the fraction of light arriving from every direction that `bsdf`
+    /// reflects toward `outgoing`, in each channel.
+    fn reflectance(bsdf: &Bsdf, outgoing: &Vec3f) -> Vector3<Float> {
+        let mut rng = Rng::new(0, 0);
+        let mut reflected = Vector3::zeros();
+        for _ in 0..10000 {
+            if let Some(sample) = bsdf.sample(outgoing, &mut rng) {
+                assert_relative_eq!(sample.pdf, bsdf.pdf(outgoing, &sample.direction));
+                let cosine = sample.direction.y;
+                reflected += sample.value.0 * (cosine / sample.pdf / 10000.0);
+            }
+        }
+        reflected
+    }
+
+    #[test]
+    fn it_reflects_light_off_microfacets() {
+        let outgoing = Vector3::new(0.3, 1.0, -0.2).normalize();
+        // A white dielectric reflects almost everything between its facets and its base,
+        // losing a little to masking.
+        for &roughness in &[0.1, 0.5, 1.0] {
+            let white = reflectance(&ggx(roughness, 0.0), &outgoing);
+            assert!(white.x <= 1.01 && white.x > 0.85, "{}", white.x);
+        }
+        // A smooth metal mirrors its base color.
+        let gold = GgxBsdf {
+            base_color: Color::new(1.0, 0.8, 0.3),
+            ..ggx(0.05, 1.0)
+        };
+        let reflected = reflectance(&gold, &outgoing);
+        assert_relative_eq!(reflected, Vector3::new(1.0, 0.8, 0.3), epsilon = 0.03);
+    }
+
+    #[test]
+    fn it_reflects_the_same_both_ways() {
+        let bsdf = GgxBsdf {
+            base_color: Color::new(0.5, 0.2, 0.1),
+            ..ggx(0.4, 0.3)
+        };
+        let a = Vector3::new(0.3, 1.0, -0.2).normalize();
+        let b = Vector3::new(-0.5, 0.6, 0.4).normalize();
+        assert_relative_eq!(bsdf.eval(&a, &b).0, bsdf.eval(&b, &a).0, epsilon = 1e-12);
+        assert_eq!(bsdf.eval(&a, &-b), Color::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_reflects_more_at_grazing_angles() {
+        let bsdf = ggx(0.2, 0.0);
+        let head_on = bsdf.fresnel(1.0);
+        assert_relative_eq!(head_on.0.x, 0.04, epsilon = 1e-12);
+        assert!(bsdf.fresnel(0.1).0.x > 0.5);
+        assert!(
+            GgxBsdf {
+                base_color: Color::new(0.0, 0.0, 0.0),
+                ..ggx(0.2, 1.0)
+            }
+            .albedo()
+                > 0.0
+        );
+    }
+}
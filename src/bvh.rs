@@ -0,0 +1,386 @@
+use na::*;
+
+use crate::bounds::*;
+use crate::ray::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// Number of buckets centroids are binned into when evaluating candidate splits.
+const BUCKET_COUNT: usize = 12;
+
+/// Nodes with at most this many shapes may become leaves when splitting is not cheaper.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Cost of visiting a node relative to intersecting a single shape.
+const TRAVERSAL_COST: Float = 0.125;
+
+enum Node {
+    Leaf {
+        bounds: BoundingBox,
+        start: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: BoundingBox,
+        left: usize,
+        right: usize,
+    },
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: BoundingBox,
+    centroid: Point3f,
+}
+
+/// A bounding volume hierarchy over a list of shapes, split using the surface area
+/// heuristic. Shapes are referred to by their index in the list the hierarchy was built
+/// from, so it must be rebuilt whenever existing shapes are changed or removed. Shapes
+/// appended after the build are still found, just without acceleration.
+#[derive(Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Shape indices ordered so that every leaf covers a contiguous run.
+    ordered: Vec<usize>,
+    /// Shapes with infinite bounds, such as planes, which every ray is tested against.
+    unbounded: Vec<usize>,
+    /// The number of shapes the hierarchy was built over.
+    indexed_count: usize,
+}
+
+impl Bvh {
+    pub fn build(objects: &[Box<Shape>]) -> Self {
+        let mut items = vec![];
+        let mut unbounded = vec![];
+        for (index, obj) in objects.iter().enumerate() {
+            let bounds = obj.bounds();
+            if bounds.is_finite() {
+                items.push(BuildItem {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                });
+            } else {
+                unbounded.push(index);
+            }
+        }
+
+        let mut bvh = Self {
+            nodes: vec![],
+            ordered: Vec::with_capacity(items.len()),
+            unbounded,
+            indexed_count: objects.len(),
+        };
+        if !items.is_empty() {
+            bvh.build_node(&mut items);
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, items: &mut [BuildItem]) -> usize {
+        let bounds = items
+            .iter()
+            .fold(BoundingBox::empty(), |b, item| b.union(&item.bounds));
+        let node_index = self.nodes.len();
+        if let Some(mid) = split(items, &bounds) {
+            // Reserve our slot so that children are numbered after us.
+            self.nodes.push(Node::Leaf {
+                bounds,
+                start: 0,
+                count: 0,
+            });
+            let (left_items, right_items) = items.split_at_mut(mid);
+            let left = self.build_node(left_items);
+            let right = self.build_node(right_items);
+            self.nodes[node_index] = Node::Interior {
+                bounds,
+                left,
+                right,
+            };
+        } else {
+            let start = self.ordered.len();
+            self.ordered.extend(items.iter().map(|item| item.index));
+            self.nodes.push(Node::Leaf {
+                bounds,
+                start,
+                count: items.len(),
+            });
+        }
+        node_index
+    }
+
+    /// Calls `visit` with the index of every shape whose bounds the ray enters before
+    /// `t_max`. `visit` returns the new `t_max`, which lets us skip nodes beyond the closest
+    /// hit so far, or None to stop traversing altogether.
+    fn traverse<F>(&self, object_count: usize, ray: &Ray, mut t_max: Float, mut visit: F)
+    where
+        F: FnMut(usize) -> Option<Float>,
+    {
+        let unindexed = self.indexed_count.min(object_count)..object_count;
+        for index in self.unbounded.iter().copied().chain(unindexed) {
+            match visit(index) {
+                Some(t) => t_max = t,
+                None => return,
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inverse_direction = Vector3::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            match self.nodes[node_index] {
+                Node::Leaf {
+                    bounds,
+                    start,
+                    count,
+                } => {
+                    if bounds
+                        .intersection(ray, &inverse_direction, t_max)
+                        .is_none()
+                    {
+                        continue;
+                    }
+                    for &index in &self.ordered[start..start + count] {
+                        match visit(index) {
+                            Some(t) => t_max = t,
+                            None => return,
+                        }
+                    }
+                }
+                Node::Interior {
+                    bounds,
+                    left,
+                    right,
+                } => {
+                    if bounds
+                        .intersection(ray, &inverse_direction, t_max)
+                        .is_some()
+                    {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn intersection<'a>(
+        &self,
+        objects: &'a [Box<Shape>],
+        ray: &Ray,
+    ) -> Option<(Float, &'a Shape)> {
+        let mut min_intersection: Option<(Float, &'a Shape)> = None;
+        self.traverse(objects.len(), ray, Float::INFINITY, |index| {
+            let obj = &*objects[index];
+            match (min_intersection, obj.intersection(ray)) {
+                (None, Some(t)) => min_intersection = Some((t, obj)),
+                (Some((min_t, _)), Some(t)) if t < min_t => {
+                    min_intersection = Some((t, obj));
+                }
+                _ => {}
+            }
+            Some(min_intersection.map_or(Float::INFINITY, |(t, _)| t))
+        });
+        min_intersection
+    }
+
+    pub fn is_occluded(
+        &self,
+        objects: &[Box<Shape>],
+        ray: &Ray,
+        distance_threshold: Float,
+    ) -> bool {
+        let mut occluded = false;
+        self.traverse(objects.len(), ray, distance_threshold, |index| {
+            if let Some(t) = objects[index].intersection(ray) {
+                if t < distance_threshold {
+                    occluded = true;
+                    return None;
+                }
+            }
+            Some(distance_threshold)
+        });
+        occluded
+    }
+}
+
+/// Partitions `items` by the cheapest split according to the surface area heuristic and
+/// returns the size of the first partition, or None when a leaf would be cheaper.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn split(items: &mut [BuildItem], bounds: &BoundingBox) -> Option<usize> {
+    if items.len() <= 1 {
+        return None;
+    }
+
+    let centroid_bounds = items
+        .iter()
+        .fold(BoundingBox::empty(), |b, item| b.include(&item.centroid));
+    let axis = centroid_bounds.longest_axis();
+    let low = centroid_bounds.min[axis];
+    let extent = centroid_bounds.max[axis] - low;
+    if extent <= 0.0 {
+        // Every centroid coincides, so no split separates anything spatially.
+        return if items.len() <= MAX_LEAF_SIZE {
+            None
+        } else {
+            Some(items.len() / 2)
+        };
+    }
+
+    let bucket_of = |item: &BuildItem| {
+        let b = ((item.centroid[axis] - low) / extent * BUCKET_COUNT as Float) as usize;
+        b.min(BUCKET_COUNT - 1)
+    };
+    let mut counts = [0; BUCKET_COUNT];
+    let mut bucket_bounds = [BoundingBox::empty(); BUCKET_COUNT];
+    for item in items.iter() {
+        let b = bucket_of(item);
+        counts[b] += 1;
+        bucket_bounds[b] = bucket_bounds[b].union(&item.bounds);
+    }
+
+    // Splitting after bucket i puts buckets 0..=i on the left.
+    let mut best = (Float::INFINITY, 0);
+    for i in 0..BUCKET_COUNT - 1 {
+        let (left_count, left_bounds) = accumulate(&counts[..=i], &bucket_bounds[..=i]);
+        let (right_count, right_bounds) = accumulate(&counts[i + 1..], &bucket_bounds[i + 1..]);
+        let cost = TRAVERSAL_COST
+            + (left_count as Float * left_bounds.surface_area()
+                + right_count as Float * right_bounds.surface_area())
+                / bounds.surface_area();
+        if cost < best.0 {
+            best = (cost, i);
+        }
+    }
+
+    let leaf_cost = items.len() as Float;
+    if items.len() <= MAX_LEAF_SIZE && best.0 >= leaf_cost {
+        return None;
+    }
+
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if bucket_of(&items[i]) <= best.1 {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    if mid == 0 || mid == items.len() {
+        return Some(items.len() / 2);
+    }
+
+    Some(mid)
+}
+
+fn accumulate(counts: &[usize], bounds: &[BoundingBox]) -> (usize, BoundingBox) {
+    (
+        counts.iter().sum(),
+        bounds
+            .iter()
+            .fold(BoundingBox::empty(), |total, b| total.union(b)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::material::*;
+    use crate::model_transformation::*;
+    use crate::plane::*;
+    use crate::sphere::*;
+
+    /// A grid of small spheres above a floor.
+    fn objects() -> Vec<Box<Shape>> {
+        let mut objects: Vec<Box<Shape>> = vec![Box::new(Plane::floor(-10.0, Material::default()))];
+        for x in -3..3 {
+            for y in -3..3 {
+                for z in -3..3 {
+                    objects.push(Box::new(Sphere::from(
+                        ModelTransformation::new()
+                            .translate(Float::from(x), Float::from(y), Float::from(z))
+                            .scale(0.3, 0.3, 0.3),
+                    )));
+                }
+            }
+        }
+        objects
+    }
+
+    /// Deterministic rays originating from around the grid.
+    // The top 53 bits of the seed convert to a float exactly.
+    #[allow(clippy::cast_precision_loss)]
+    fn rays() -> Vec<Ray> {
+        let mut seed: u64 = 42;
+        let mut next = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as Float / (1u64 << 53) as Float * 2.0 - 1.0
+        };
+        (0..200)
+            .map(|_| Ray {
+                origin: Point3::new(next() * 5.0, next() * 5.0, next() * 5.0),
+                direction: Vector3::new(next(), next(), next()).normalize(),
+            })
+            .collect()
+    }
+
+    fn linear_intersection(objects: &[Box<Shape>], ray: &Ray) -> Option<Float> {
+        objects
+            .iter()
+            .filter_map(|obj| obj.intersection(ray))
+            .fold(None, |min, t| match min {
+                Some(m) if m <= t => Some(m),
+                _ => Some(t),
+            })
+    }
+
+    #[test]
+    fn it_finds_same_intersections_as_linear_search() {
+        let objects = objects();
+        let bvh = Bvh::build(&objects);
+        for ray in rays() {
+            let expected = linear_intersection(&objects, &ray);
+            let actual = bvh.intersection(&objects, &ray).map(|(t, _)| t);
+            assert_eq!(expected.is_some(), actual.is_some());
+            if let (Some(e), Some(a)) = (expected, actual) {
+                assert_relative_eq!(e, a);
+            }
+        }
+    }
+
+    #[test]
+    fn it_finds_same_occlusion_as_linear_search() {
+        let objects = objects();
+        let bvh = Bvh::build(&objects);
+        for ray in rays() {
+            let expected = linear_intersection(&objects, &ray).is_some_and(|t| t < 3.0);
+            assert_eq!(bvh.is_occluded(&objects, &ray, 3.0), expected);
+        }
+    }
+
+    #[test]
+    fn it_finds_shapes_added_after_build() {
+        let mut objects: Vec<Box<Shape>> = vec![];
+        let bvh = Bvh::build(&objects);
+        objects.push(Box::new(Sphere::from(ModelTransformation::new())));
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert_relative_eq!(bvh.intersection(&objects, &r).unwrap().0, 4.0);
+    }
+}
@@ -1,17 +1,63 @@
 use na::*;
 
+use std::f64::consts::*;
+
 use crate::ray::*;
+use crate::sampling::*;
 use crate::types::*;
 
+/// How points on the canvas map to directions out of the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// A pinhole looking through a flat canvas, which spans the field of view across its
+    /// longer side.
+    Perspective,
+    /// Parallel rays leaving a canvas `width` world units wide, so sizes do not shrink
+    /// with distance.
+    Orthographic { width: Float },
+    /// An equidistant fisheye: the angle away from the view direction grows linearly with
+    /// the distance from the canvas center, reaching half the field of view at the middle
+    /// of the canvas's longer edges.
+    Fisheye,
+    /// A full 360 by 180 degree panorama, with longitude across the canvas and latitude
+    /// down it. The field of view is ignored.
+    Equirectangular,
+}
+
 pub struct Camera {
     pub canvas_width: u32,
     pub canvas_height: u32,
     pub field_of_view_radians: Float,
     pub transform: Isometry3<Float>,
+    pub projection: Projection,
+    /// Radius of the thin lens in world space units. Zero makes a pinhole camera with
+    /// everything in focus.
+    pub aperture_radius: Float,
+    /// Distance from the camera to the plane that is in perfect focus. Fisheye and
+    /// equirectangular cameras focus on a sphere of this radius instead.
+    pub focal_distance: Float,
+    /// Number of blades forming a polygonal aperture, which shapes out-of-focus
+    /// highlights. Fewer than three makes the aperture a circle.
+    pub aperture_blades: u32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            canvas_width: 640,
+            canvas_height: 480,
+            field_of_view_radians: FRAC_PI_2,
+            transform: Isometry3::identity(),
+            projection: Projection::Perspective,
+            aperture_radius: 0.0,
+            focal_distance: 1.0,
+            aperture_blades: 0,
+        }
+    }
 }
 
 impl Camera {
-    /// Computes the size of a pixel in world space units.
+    /// Computes the size of a pixel in world space units, for the perspective projection.
     pub fn compute_pixel_size(&self) -> (Float, Float, Float) {
         let half_view = (self.field_of_view_radians / 2.0).tan();
         let aspect_ratio = f64::from(self.canvas_width) / f64::from(self.canvas_height);
@@ -27,23 +73,132 @@ impl Camera {
         )
     }
 
+    #[allow(dead_code)]
     pub fn ray_for_pixel(&self, pixel_x: u32, pixel_y: u32) -> Ray {
-        let (half_width, half_height, pixel_size) = self.compute_pixel_size();
         // Center the pixel.
-        let x_offset = (f64::from(pixel_x) + 0.5) * pixel_size;
-        let y_offset = (f64::from(pixel_y) + 0.5) * pixel_size;
+        self.ray_through(f64::from(pixel_x) + 0.5, f64::from(pixel_y) + 0.5)
+    }
+
+    /// Casts a ray through a point on the canvas given in pixels, measured from the
+    /// top-left corner of the canvas.
+    pub fn ray_through(&self, canvas_x: Float, canvas_y: Float) -> Ray {
+        let (origin, direction) = self.camera_space_ray(canvas_x, canvas_y);
+        let inv = self.transform.inverse();
+        Ray {
+            origin: inv * origin,
+            direction: inv * direction,
+        }
+    }
+
+    /// The ray through a point on the canvas in camera space, where the camera looks down
+    /// -z. Like the perspective projection, the left of the canvas is towards +x.
+    fn camera_space_ray(&self, canvas_x: Float, canvas_y: Float) -> (Point3f, Vec3f) {
+        let width = Float::from(self.canvas_width);
+        let height = Float::from(self.canvas_height);
+        // Pixels from the canvas center, with +y up.
+        let (x, y) = (width / 2.0 - canvas_x, height / 2.0 - canvas_y);
+
+        match self.projection {
+            Projection::Perspective => {
+                let (half_width, half_height, pixel_size) = self.compute_pixel_size();
+                let x_offset = canvas_x * pixel_size;
+                let y_offset = canvas_y * pixel_size;
+
+                let world_x = half_width - x_offset;
+                let world_y = half_height - y_offset;
+
+                // Assumption: The camera and canvas are 1 unit apart with the canvas
+                // situated at z = -1.
+                let direction = Vector3::new(world_x, world_y, -1.0).normalize();
+                (Point3::origin(), direction)
+            }
+            Projection::Orthographic { width: world_width } => {
+                let pixel_size = world_width / width;
+                (
+                    Point3::new(x * pixel_size, y * pixel_size, 0.0),
+                    -Vector3::z(),
+                )
+            }
+            Projection::Fisheye => {
+                let half_size = width.max(height) / 2.0;
+                let theta = (x * x + y * y).sqrt() / half_size * self.field_of_view_radians / 2.0;
+                let phi = y.atan2(x);
+                let direction = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    -theta.cos(),
+                );
+                (Point3::origin(), direction)
+            }
+            Projection::Equirectangular => {
+                let longitude = x / width * 2.0 * PI;
+                let latitude = y / height * PI;
+                let direction = Vector3::new(
+                    longitude.sin() * latitude.cos(),
+                    latitude.sin(),
+                    -longitude.cos() * latitude.cos(),
+                );
+                (Point3::origin(), direction)
+            }
+        }
+    }
 
-        let world_x = half_width - x_offset;
-        let world_y = half_height - y_offset;
+    /// Like `ray_through`, but the ray leaves from a point on the lens chosen by `lens`
+    /// in [0, 1)² and passes through the point in focus the pinhole ray would hit, so only
+    /// objects at the focal distance stay sharp.
+    pub fn ray_through_lens(&self, canvas_x: Float, canvas_y: Float, lens: (Float, Float)) -> Ray {
+        let pinhole = self.ray_through(canvas_x, canvas_y);
+        if self.aperture_radius <= 0.0 {
+            return pinhole;
+        }
+
+        // Work in camera space, where the camera looks down -z.
+        let origin = self.transform * pinhole.origin;
+        let direction = self.transform * pinhole.direction;
+        let (lens_x, lens_y) = self.sample_aperture(lens);
+        let (lens_x, lens_y) = (lens_x * self.aperture_radius, lens_y * self.aperture_radius);
+        let (focus, lens_point) = match self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => (
+                origin + direction * (self.focal_distance / -direction.z),
+                origin + Vector3::new(lens_x, lens_y, 0.0),
+            ),
+            // Panoramic rays can point anywhere, so the lens faces along each ray.
+            Projection::Fisheye | Projection::Equirectangular => {
+                let (right, up) = orthonormal_basis(&direction);
+                (
+                    origin + direction * self.focal_distance,
+                    origin + right * lens_x + up * lens_y,
+                )
+            }
+        };
 
         let inv = self.transform.inverse();
-        // Assumption: The camera and canvas are 1 unit apart with the canvas situated at
-        // z = -1.
-        let pixel = inv * Point3::new(world_x, world_y, -1.0);
-        let origin = inv * Point3::new(0.0, 0.0, 0.0);
-        let direction = (pixel - origin).normalize();
+        Ray {
+            origin: inv * lens_point,
+            direction: inv * (focus - lens_point).normalize(),
+        }
+    }
 
-        Ray { origin, direction }
+    /// Maps a point in [0, 1)² to a point on the unit aperture, uniformly by area.
+    fn sample_aperture(&self, (u, v): (Float, Float)) -> (Float, Float) {
+        if self.aperture_blades < 3 {
+            return concentric_disk(u, v);
+        }
+
+        // Pick one of the triangles fanning out from the center, then a point within it.
+        let blades = Float::from(self.aperture_blades);
+        let scaled = u * blades;
+        let blade = scaled.floor();
+        let u = scaled - blade;
+        let angle = |i: Float| 2.0 * PI * i / blades;
+        let (start, end) = (angle(blade), angle(blade + 1.0));
+        // Uniform over the triangle between the center and the corners at `start` and `end`.
+        let radius = u.sqrt();
+        let (s, t) = (radius * (1.0 - v), radius * v);
+        (
+            s * start.cos() + t * end.cos(),
+            s * start.sin() + t * end.sin(),
+        )
     }
 }
 
@@ -54,6 +209,7 @@ mod tests {
     use approx::*;
 
     use super::*;
+    use crate::view_transformation::*;
 
     #[test]
     fn it_computes_horizontal_canvas_pixel_size() {
@@ -62,6 +218,7 @@ mod tests {
             canvas_height: 125,
             field_of_view_radians: FRAC_PI_2,
             transform: Isometry3::identity(),
+            ..Camera::default()
         };
         assert_relative_eq!(c.compute_pixel_size().2, 0.01);
     }
@@ -73,6 +230,7 @@ mod tests {
             canvas_height: 200,
             field_of_view_radians: FRAC_PI_2,
             transform: Isometry3::identity(),
+            ..Camera::default()
         };
         assert_relative_eq!(c.compute_pixel_size().2, 0.01);
     }
@@ -84,6 +242,7 @@ mod tests {
             canvas_height: 101,
             field_of_view_radians: FRAC_PI_2,
             transform: Isometry3::identity(),
+            ..Camera::default()
         };
         let r = c.ray_for_pixel(100, 50);
         assert_relative_eq!(r.origin, Point3::new(0.0, 0.0, 0.0));
@@ -97,6 +256,7 @@ mod tests {
             canvas_height: 101,
             field_of_view_radians: FRAC_PI_2,
             transform: Isometry3::identity(),
+            ..Camera::default()
         };
         let r = c.ray_for_pixel(0, 0);
         assert_relative_eq!(r.origin, Point3::new(0.0, 0.0, 0.0));
@@ -117,6 +277,7 @@ mod tests {
             canvas_height: 101,
             field_of_view_radians: FRAC_PI_2,
             transform: t,
+            ..Camera::default()
         };
         let r = c.ray_for_pixel(100, 50);
         assert_relative_eq!(r.origin, Point3::new(0.0, 2.0, -5.0));
@@ -127,4 +288,157 @@ mod tests {
             epsilon = 1e-15
         ));
     }
+
+    fn lens_camera(aperture_blades: u32) -> Camera {
+        Camera {
+            canvas_width: 201,
+            canvas_height: 101,
+            transform: ViewTransformation {
+                from: Point3::new(1.0, 2.0, 3.0),
+                to: Point3::new(1.0, 2.0, 0.0),
+                up: Vector3::y(),
+            }
+            .matrix(),
+            aperture_radius: 0.5,
+            focal_distance: 3.0,
+            aperture_blades,
+            ..Camera::default()
+        }
+    }
+
+    #[test]
+    fn it_focuses_lens_rays_on_focal_plane() {
+        for blades in &[0, 6] {
+            let c = lens_camera(*blades);
+            let pinhole = c.ray_through(40.0, 70.0);
+            let focus = pinhole.point_at(3.0 / -pinhole.direction.z);
+            for lens in &[(0.1, 0.2), (0.9, 0.5), (0.5, 0.99)] {
+                let r = c.ray_through_lens(40.0, 70.0, *lens);
+                assert_relative_eq!(r.origin.z, 3.0, epsilon = 1e-12);
+                assert!((r.origin - pinhole.origin).norm() <= 0.5 + 1e-12);
+                assert!(relative_eq!(
+                    r.point_at((focus.z - r.origin.z) / r.direction.z),
+                    focus,
+                    epsilon = 1e-9
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn it_is_a_pinhole_without_aperture() {
+        let c = Camera {
+            aperture_radius: 0.0,
+            ..lens_camera(0)
+        };
+        let r = c.ray_through_lens(40.0, 70.0, (0.9, 0.1));
+        let pinhole = c.ray_through(40.0, 70.0);
+        assert_relative_eq!(r.origin, pinhole.origin);
+        assert_relative_eq!(r.direction, pinhole.direction);
+    }
+
+    #[test]
+    fn it_samples_polygonal_apertures() {
+        let c = lens_camera(5);
+        // The inscribed circle of a pentagon has radius cos(36°).
+        let apothem = (PI / 5.0).cos();
+        let mut outside_circle = 0;
+        for i in 0..20 {
+            for j in 0..20 {
+                let (x, y) = c.sample_aperture((Float::from(i) / 20.0, Float::from(j) / 20.0));
+                let r = (x * x + y * y).sqrt();
+                assert!(r <= 1.0 + 1e-12);
+                if r > apothem {
+                    outside_circle += 1;
+                }
+            }
+        }
+        // Some points reach into the corners, beyond the inscribed circle.
+        assert!(outside_circle > 0);
+    }
+
+    fn projected_camera(projection: Projection) -> Camera {
+        Camera {
+            canvas_width: 200,
+            canvas_height: 100,
+            field_of_view_radians: PI,
+            projection,
+            ..Camera::default()
+        }
+    }
+
+    #[test]
+    fn it_casts_parallel_orthographic_rays() {
+        let c = projected_camera(Projection::Orthographic { width: 4.0 });
+        let center = c.ray_through(100.0, 50.0);
+        assert_relative_eq!(center.origin, Point3::new(0.0, 0.0, 0.0));
+        assert_relative_eq!(center.direction, Vector3::new(0.0, 0.0, -1.0));
+        let corner = c.ray_through(0.0, 0.0);
+        assert_relative_eq!(corner.origin, Point3::new(2.0, 1.0, 0.0));
+        assert_relative_eq!(corner.direction, Vector3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn it_casts_equidistant_fisheye_rays() {
+        let c = projected_camera(Projection::Fisheye);
+        assert_relative_eq!(
+            c.ray_through(100.0, 50.0).direction,
+            Vector3::new(0.0, 0.0, -1.0)
+        );
+        // Half way to the edge is half of the 90 degrees to the side.
+        let r = c.ray_through(150.0, 50.0);
+        let k = FRAC_PI_4.sin();
+        assert!(relative_eq!(
+            r.direction,
+            Vector3::new(-k, 0.0, -k),
+            epsilon = 1e-12
+        ));
+        assert!(relative_eq!(
+            c.ray_through(200.0, 50.0).direction,
+            Vector3::new(-1.0, 0.0, 0.0),
+            epsilon = 1e-12
+        ));
+    }
+
+    #[test]
+    fn it_casts_equirectangular_rays() {
+        let c = projected_camera(Projection::Equirectangular);
+        let direction = |x, y| c.ray_through(x, y).direction;
+        assert_relative_eq!(direction(100.0, 50.0), Vector3::new(0.0, 0.0, -1.0));
+        assert!(relative_eq!(
+            direction(50.0, 50.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            epsilon = 1e-12
+        ));
+        assert!(relative_eq!(
+            direction(0.0, 50.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            epsilon = 1e-12
+        ));
+        assert!(relative_eq!(
+            direction(100.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            epsilon = 1e-12
+        ));
+    }
+
+    #[test]
+    fn it_focuses_panoramas_on_a_sphere() {
+        let c = Camera {
+            aperture_radius: 0.5,
+            focal_distance: 2.0,
+            ..projected_camera(Projection::Equirectangular)
+        };
+        let pinhole = c.ray_through(30.0, 20.0);
+        let focus = pinhole.point_at(2.0);
+        let r = c.ray_through_lens(30.0, 20.0, (0.8, 0.3));
+        assert!((r.origin - pinhole.origin).norm() > 0.0);
+        assert_relative_eq!(
+            r.origin.coords.dot(&pinhole.direction),
+            0.0,
+            epsilon = 1e-12
+        );
+        let t = (focus - r.origin).norm();
+        assert!(relative_eq!(r.point_at(t), focus, epsilon = 1e-12));
+    }
 }
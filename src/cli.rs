@@ -0,0 +1,401 @@
+use std::path::PathBuf;
+
+use crate::exr::*;
+use crate::filter::*;
+use crate::image_output::*;
+use crate::integrator::*;
+use crate::renderer::*;
+use crate::sample_pattern::*;
+use crate::tone_mapping::*;
+use crate::types::*;
+
+pub const USAGE: &str = "Usage: rust-raytracer [options] <scene file>
+
+Options:
+  -o, --output <path>    Where to write the image, as .ppm, .png, .hdr or .exr
+                         (default: out.ppm)
+      --bit-depth <bits> Bits per channel in .ppm and .png images, 8 or 16 (default: 8)
+      --exr-compression <none|zip>
+                         How to compress .exr images (default: zip)
+      --tone-map <clamp|reinhard|reinhard-extended|aces>
+                         How .ppm and .png images squeeze in bright light (default: clamp)
+      --white-point <radiance>
+                         The radiance that maps to white with reinhard-extended
+                         (default: the brightest pixel)
+      --exposure <stops> Brighten or darken .ppm and .png images (default: 0)
+      --width <pixels>   Override the canvas width of the scene's camera
+      --height <pixels>  Override the canvas height of the scene's camera
+  -j, --threads <n>      Number of render threads (default: all cores)
+  -s, --samples <n>      Rays per pixel, or the most any pixel gets with --adaptive;
+                         grid and jittered round up to a square number (default: 1)
+      --adaptive <error> Stop sampling a pixel once the standard error of its luminance
+                         is at most this
+      --min-samples <n>  Rays per pixel between checks with --adaptive (default: 4)
+      --sample-counts <path>
+                         Also write an image of how many rays each pixel took, where
+                         white is the most allowed and gray levels are linear
+      --pattern <grid|jittered|halton|sobol>
+                         Where in each pixel the rays go (default: grid)
+      --filter <box|tent|gaussian|mitchell>
+                         How the rays are weighted into pixels (default: box)
+      --integrator <whitted|path>
+                         Quick Phong shading or full global illumination by path
+                         tracing (default: whitted)
+  -q, --quiet            Only print errors
+  -h, --help             Print this message";
+
+/// The process exit codes the renderer reports.
+pub mod exit_code {
+    pub const USAGE: i32 = 2;
+    pub const SCENE: i32 = 3;
+    pub const OUTPUT: i32 = 4;
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub scene_path: PathBuf,
+    pub output_path: PathBuf,
+    pub output_settings: OutputSettings,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub threads: Option<usize>,
+    pub samples_per_pixel: u32,
+    pub pattern: SamplePattern,
+    pub filter: Filter,
+    pub integrator: Integrator,
+    pub adaptive: Option<AdaptiveSampling>,
+    pub sample_counts_path: Option<PathBuf>,
+    pub quiet: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Render(Options),
+    Help,
+}
+
+/// Parses the arguments following the program name. Options taking a value accept it
+/// either as the next argument or after an `=`.
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut scene_path = None;
+    let mut white_point = None;
+    let mut min_samples = None;
+    let mut options = Options {
+        scene_path: PathBuf::new(),
+        output_path: PathBuf::from("out.ppm"),
+        output_settings: OutputSettings::default(),
+        width: None,
+        height: None,
+        threads: None,
+        samples_per_pixel: 1,
+        pattern: SamplePattern::Grid,
+        filter: Filter::Box,
+        integrator: Integrator::Whitted,
+        adaptive: None,
+        sample_counts_path: None,
+        quiet: false,
+    };
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            if scene_path.is_some() {
+                return Err(format!("unexpected argument `{arg}`"));
+            }
+            scene_path = Some(PathBuf::from(arg));
+            continue;
+        }
+
+        let (flag, inline_value) = match arg.find('=') {
+            Some(i) => (&arg[..i], Some(arg[i + 1..].to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("`{flag}` expects a value"))
+        };
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-q" | "--quiet" => options.quiet = true,
+            "-o" | "--output" => options.output_path = PathBuf::from(value()?),
+            "--bit-depth" | "--exr-compression" | "--tone-map" | "--pattern" | "--filter"
+            | "--integrator" => set_choice(&mut options, flag, &value()?)?,
+            "--white-point" => white_point = Some(parse_positive::<Float>(flag, &value()?)?),
+            "--exposure" => {
+                options.output_settings.tone_mapper.exposure = value()?
+                    .parse::<Float>()
+                    .ok()
+                    .filter(|stops| stops.is_finite())
+                    .ok_or_else(|| format!("`{flag}` expects a number of stops"))?;
+            }
+            "--width" => options.width = Some(parse_positive(flag, &value()?)?),
+            "--height" => options.height = Some(parse_positive(flag, &value()?)?),
+            "-j" | "--threads" => options.threads = Some(parse_positive(flag, &value()?)?),
+            "-s" | "--samples" => options.samples_per_pixel = parse_positive(flag, &value()?)?,
+            "--adaptive" => {
+                options.adaptive = Some(AdaptiveSampling {
+                    min_samples: DEFAULT_MIN_SAMPLES,
+                    threshold: parse_positive(flag, &value()?)?,
+                });
+            }
+            "--min-samples" => min_samples = Some(parse_positive(flag, &value()?)?),
+            "--sample-counts" => options.sample_counts_path = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown option `{flag}`")),
+        }
+    }
+
+    options.scene_path = scene_path.ok_or_else(|| "missing scene file".to_string())?;
+    if let Some(white_point) = white_point {
+        match &mut options.output_settings.tone_mapper.operator {
+            ToneMapping::ExtendedReinhard { white_point: w } => *w = Some(white_point),
+            _ => return Err("`--white-point` needs `--tone-map reinhard-extended`".to_string()),
+        }
+    }
+    if let Some(min_samples) = min_samples {
+        match &mut options.adaptive {
+            Some(adaptive) => adaptive.min_samples = min_samples,
+            None => return Err("`--min-samples` needs `--adaptive`".to_string()),
+        }
+    }
+    for path in Some(&options.output_path)
+        .into_iter()
+        .chain(&options.sample_counts_path)
+    {
+        if OutputFormat::from_path(path).is_none() {
+            return Err(format!(
+                "cannot tell the image format of `{}`, use .ppm, .png, .hdr or .exr",
+                path.display()
+            ));
+        }
+    }
+    Ok(Command::Render(options))
+}
+
+fn parse_positive<T>(flag: &str, value: &str) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + Default,
+{
+    match value.parse::<T>() {
+        Ok(n) if n > T::default() => Ok(n),
+        _ => Err(format!("`{flag}` expects a positive number")),
+    }
+}
+
+/// Sets the option `flag` picks one of a fixed set of names for.
+fn set_choice(options: &mut Options, flag: &str, value: &str) -> Result<(), String> {
+    match flag {
+        "--bit-depth" => {
+            options.output_settings.bit_depth = parse_choice(
+                flag,
+                value,
+                &[("8", BitDepth::Eight), ("16", BitDepth::Sixteen)],
+            )?;
+        }
+        "--exr-compression" => {
+            options.output_settings.exr_compression = parse_choice(
+                flag,
+                value,
+                &[("none", ExrCompression::None), ("zip", ExrCompression::Zip)],
+            )?;
+        }
+        "--tone-map" => {
+            options.output_settings.tone_mapper.operator = parse_choice(
+                flag,
+                value,
+                &[
+                    ("clamp", ToneMapping::Clamp),
+                    ("reinhard", ToneMapping::Reinhard),
+                    (
+                        "reinhard-extended",
+                        ToneMapping::ExtendedReinhard { white_point: None },
+                    ),
+                    ("aces", ToneMapping::Aces),
+                ],
+            )?;
+        }
+        "--pattern" => {
+            options.pattern = parse_choice(
+                flag,
+                value,
+                &[
+                    ("grid", SamplePattern::Grid),
+                    ("jittered", SamplePattern::Jittered),
+                    ("halton", SamplePattern::Halton),
+                    ("sobol", SamplePattern::Sobol),
+                ],
+            )?;
+        }
+        "--filter" => {
+            options.filter = parse_choice(
+                flag,
+                value,
+                &[
+                    ("box", Filter::Box),
+                    ("tent", Filter::Tent),
+                    ("gaussian", Filter::Gaussian),
+                    ("mitchell", Filter::Mitchell),
+                ],
+            )?;
+        }
+        "--integrator" => {
+            options.integrator = parse_choice(
+                flag,
+                value,
+                &[
+                    ("whitted", Integrator::Whitted),
+                    ("path", Integrator::PathTracer),
+                ],
+            )?;
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Looks `value` up among the names in `choices`, or lists them in the error.
+fn parse_choice<T: Copy>(flag: &str, value: &str, choices: &[(&str, T)]) -> Result<T, String> {
+    if let Some(&(_, choice)) = choices.iter().find(|(name, _)| *name == value) {
+        return Ok(choice);
+    }
+    let names: Vec<_> = choices.iter().map(|(name, _)| *name).collect();
+    let (last, rest) = names.split_last().expect("choices are not empty");
+    Err(format!("`{flag}` expects {} or {last}", rest.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn it_parses_scene_with_defaults() {
+        assert_eq!(
+            parse(&["room.scene"]),
+            Ok(Command::Render(Options {
+                scene_path: PathBuf::from("room.scene"),
+                output_path: PathBuf::from("out.ppm"),
+                output_settings: OutputSettings::default(),
+                width: None,
+                height: None,
+                threads: None,
+                samples_per_pixel: 1,
+                pattern: SamplePattern::Grid,
+                filter: Filter::Box,
+                integrator: Integrator::Whitted,
+                adaptive: None,
+                sample_counts_path: None,
+                quiet: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn it_parses_every_option() {
+        assert_eq!(
+            parse(&[
+                "-o",
+                "render.png",
+                "--bit-depth=16",
+                "--exr-compression",
+                "none",
+                "--tone-map=reinhard-extended",
+                "--white-point",
+                "4.5",
+                "--exposure",
+                "-1.5",
+                "--width=1920",
+                "--height",
+                "1080",
+                "-j",
+                "32",
+                "--samples=16",
+                "--pattern",
+                "sobol",
+                "--filter=mitchell",
+                "--integrator",
+                "path",
+                "--adaptive",
+                "0.01",
+                "--min-samples=8",
+                "--sample-counts",
+                "counts.png",
+                "-q",
+                "room.scene",
+            ]),
+            Ok(Command::Render(Options {
+                scene_path: PathBuf::from("room.scene"),
+                output_path: PathBuf::from("render.png"),
+                output_settings: OutputSettings {
+                    bit_depth: BitDepth::Sixteen,
+                    exr_compression: ExrCompression::None,
+                    tone_mapper: ToneMapper {
+                        operator: ToneMapping::ExtendedReinhard {
+                            white_point: Some(4.5)
+                        },
+                        exposure: -1.5,
+                        srgb: true,
+                    },
+                },
+                width: Some(1920),
+                height: Some(1080),
+                threads: Some(32),
+                samples_per_pixel: 16,
+                pattern: SamplePattern::Sobol,
+                filter: Filter::Mitchell,
+                integrator: Integrator::PathTracer,
+                adaptive: Some(AdaptiveSampling {
+                    min_samples: 8,
+                    threshold: 0.01,
+                }),
+                sample_counts_path: Some(PathBuf::from("counts.png")),
+                quiet: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn it_asks_for_help() {
+        assert_eq!(parse(&["room.scene", "--help"]), Ok(Command::Help));
+    }
+
+    #[test]
+    fn it_rejects_bad_arguments() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.scene", "b.scene"]).is_err());
+        assert!(parse(&["--bogus", "a.scene"]).is_err());
+        assert!(parse(&["a.scene", "--width"]).is_err());
+        assert!(parse(&["a.scene", "--width", "wide"]).is_err());
+        assert!(parse(&["a.scene", "--threads", "0"]).is_err());
+        assert!(parse(&["a.scene", "--bit-depth", "12"]).is_err());
+        assert!(parse(&["a.scene", "--exr-compression", "piz"]).is_err());
+        assert!(parse(&["a.scene", "-o", "out.jpg"]).is_err());
+        assert!(parse(&["a.scene", "--tone-map", "filmic"]).is_err());
+        assert!(parse(&["a.scene", "--pattern", "poisson"]).is_err());
+        assert!(parse(&["a.scene", "--filter", "lanczos"]).is_err());
+        assert!(parse(&["a.scene", "--integrator", "bidirectional"]).is_err());
+        assert!(parse(&["a.scene", "--min-samples", "4"]).is_err());
+        assert!(parse(&["a.scene", "--sample-counts", "counts.txt"]).is_err());
+        assert!(parse(&["a.scene", "--exposure", "bright"]).is_err());
+        assert!(parse(&["a.scene", "--white-point", "2"]).is_err());
+    }
+
+    #[test]
+    fn it_lists_the_choices_for_an_unknown_name() {
+        assert_eq!(
+            parse(&["a.scene", "--pattern", "poisson"]),
+            Err("`--pattern` expects grid, jittered, halton or sobol".to_string())
+        );
+        assert_eq!(
+            parse(&["a.scene", "--bit-depth=12"]),
+            Err("`--bit-depth` expects 8 or 16".to_string())
+        );
+    }
+}
@@ -10,9 +10,10 @@ impl Color {
         Color(Vector3::new(r, g, b))
     }
 
-    /// Produces a bitmap-compatible u8 array with members in [0, 255].
+    /// Produces a bitmap-compatible u8 array with members in [0, 255]. Radiance outside
+    /// [0, 1] is clamped.
     pub fn to_u8_array(&self) -> [u8; 3] {
-        let Color(v) = self;
+        let Color(v) = self.clamp();
         [
             (v.x * 255.0).round() as u8,
             (v.y * 255.0).round() as u8,
@@ -20,6 +21,18 @@ impl Color {
         ]
     }
 
+    /// Like `to_u8_array` but with members in [0, 65535], for 16-bit output.
+    // Clamping first keeps the rounded values within range.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    pub fn to_u16_array(self) -> [u16; 3] {
+        let Color(v) = self.clamp();
+        [
+            (v.x * 65535.0).round() as u16,
+            (v.y * 65535.0).round() as u16,
+            (v.z * 65535.0).round() as u16,
+        ]
+    }
+
     /// Apparently this is technically called the Hadamard or Schur product.
     pub fn mix(self, other: Self) -> Self {
         let Color(u) = self;
@@ -29,7 +42,22 @@ impl Color {
 
     pub fn clamp(self) -> Self {
         let Color(v) = self;
-        Self::new(v.x.min(1.0), v.y.min(1.0), v.z.min(1.0))
+        Self::new(
+            v.x.clamp(0.0, 1.0),
+            v.y.clamp(0.0, 1.0),
+            v.z.clamp(0.0, 1.0),
+        )
+    }
+
+    /// Relative luminance of linear Rec. 709 primaries.
+    pub fn luminance(self) -> Float {
+        let Color(v) = self;
+        0.2126 * v.x + 0.7152 * v.y + 0.0722 * v.z
+    }
+
+    pub fn max_component(self) -> Float {
+        let Color(v) = self;
+        v.x.max(v.y).max(v.z)
     }
 }
 
@@ -47,4 +75,10 @@ mod tests {
         let Color(u) = a.mix(b);
         assert_relative_eq!(u, Vector3::new(0.9, 0.2, 0.04));
     }
+
+    #[test]
+    fn it_clamps_when_quantizing() {
+        assert_eq!(Color::new(4.0, -0.5, 0.5).to_u8_array(), [255, 0, 128]);
+        assert_eq!(Color::new(4.0, -0.5, 1.0).to_u16_array(), [65535, 0, 65535]);
+    }
 }
@@ -0,0 +1,267 @@
+use std::f64::consts::*;
+
+use na::*;
+
+use crate::bounds::*;
+use crate::color::*;
+use crate::intersections::*;
+use crate::material::*;
+use crate::model_transformation::*;
+use crate::object_transform::*;
+use crate::ray::*;
+use crate::sampling::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// A double-napped cone about the object space y axis whose radius at any height is the
+/// magnitude of that height, spanning `minimum` to `maximum` along the axis. Closed cones
+/// have end caps.
+pub struct Cone {
+    pub object_to_world_space: ObjectTransform,
+    pub material: Material,
+    pub minimum: Float,
+    pub maximum: Float,
+    pub closed: bool,
+}
+
+impl From<ModelTransformation> for Cone {
+    fn from(t: ModelTransformation) -> Self {
+        Self {
+            object_to_world_space: t.matrix().into(),
+            material: Material::default(),
+            minimum: -1.0,
+            maximum: 0.0,
+            closed: false,
+        }
+    }
+}
+
+impl Cone {
+    /// The object space areas of the side and of the bottom and top caps, or None if the
+    /// cone has no area or goes on forever.
+    fn object_areas(&self) -> Option<[Float; 3]> {
+        // The side's area above the apex up to height y, negated below it.
+        let side = |y: Float| SQRT_2 * PI * y * y.abs();
+        let cap = |y: Float| if self.closed { PI * y * y } else { 0.0 };
+        let areas = [
+            side(self.maximum) - side(self.minimum),
+            cap(self.minimum),
+            cap(self.maximum),
+        ];
+        let total: Float = areas.iter().sum();
+        if total.is_finite() && total > 0.0 {
+            Some(areas)
+        } else {
+            None
+        }
+    }
+
+    fn object_normal(&self, p: &Point3f) -> Vec3f {
+        let distance = p.x.powi(2) + p.z.powi(2);
+        if distance < self.maximum.powi(2) && p.y >= self.maximum - BIAS {
+            Vector3::y()
+        } else if distance < self.minimum.powi(2) && p.y <= self.minimum + BIAS {
+            -Vector3::y()
+        } else {
+            // The slope of the cone's side is 1, so the normal tilts away from the apex by
+            // as much as the point is from the axis.
+            let y = if p.y > 0.0 {
+                -distance.sqrt()
+            } else {
+                distance.sqrt()
+            };
+            Vector3::new(p.x, y, p.z)
+        }
+    }
+}
+
+impl Shape for Cone {
+    fn intersection(&self, ray: &Ray) -> Option<Float> {
+        ray_cone_intersection(
+            &self.object_to_world_space.ray_to_object_space(ray),
+            self.minimum,
+            self.maximum,
+            self.closed,
+        )
+        .into_iter()
+        .find(|t| *t >= 0.0)
+    }
+
+    fn normal_at(&self, world_point: Point3f) -> Vec3f {
+        let p = self
+            .object_to_world_space
+            .point_to_object_space(&world_point);
+        self.object_to_world_space
+            .normal_to_world_space(&self.object_normal(&p))
+    }
+
+    fn color_at(&self, world_point: &Point3f) -> Color {
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        self.material.color.color_at(object_point)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        let radius = self.minimum.abs().max(self.maximum.abs());
+        self.object_to_world_space
+            .bounds_to_world_space(&BoundingBox::from_points(&[
+                Point3::new(-radius, self.minimum, -radius),
+                Point3::new(radius, self.maximum, radius),
+            ]))
+    }
+
+    fn area(&self) -> Float {
+        self.object_areas().map_or(0.0, |areas| {
+            areas.iter().sum::<Float>() * self.object_to_world_space.mean_area_scale()
+        })
+    }
+
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        // Pick the side or a cap in proportion to its area, then a point on it.
+        let areas = self.object_areas()?;
+        let (part, u) = Distribution::new(&areas).sample(u);
+        let (object_point, object_normal) = if part == 0 {
+            // The side's area grows with the square of the height, signed so that it
+            // keeps growing through the apex.
+            let signed_square = |y: Float| y * y.abs();
+            let (low, high) = (signed_square(self.minimum), signed_square(self.maximum));
+            let square = low + u * (high - low);
+            let height = square.signum() * square.abs().sqrt();
+            let angle = 2.0 * PI * v;
+            let (cos, sin) = (angle.cos(), angle.sin());
+            (
+                Point3::new(height.abs() * cos, height, height.abs() * sin),
+                Vector3::new(cos, -height.signum(), sin) / SQRT_2,
+            )
+        } else {
+            let disk = concentric_disk(u, v);
+            let (height, normal) = if part == 1 {
+                (self.minimum, -Vector3::y())
+            } else {
+                (self.maximum, Vector3::y())
+            };
+            let radius = height.abs();
+            (
+                Point3::new(disk.0 * radius, height, disk.1 * radius),
+                normal,
+            )
+        };
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &object_normal,
+            1.0 / areas.iter().sum::<Float>(),
+        ))
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        let areas = self.object_areas()?;
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &self.object_normal(&object_point).normalize(),
+            1.0 / areas.iter().sum::<Float>(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_computes_side_normal() {
+        let cone = Cone {
+            minimum: -2.0,
+            maximum: 2.0,
+            ..Cone::from(ModelTransformation::new())
+        };
+        assert_relative_eq!(
+            cone.normal_at(Point3::new(1.0, 1.0, 1.0)),
+            Vector3::new(1.0, -(2.0).sqrt(), 1.0).normalize()
+        );
+        assert_relative_eq!(
+            cone.normal_at(Point3::new(-1.0, -1.0, 0.0)),
+            Vector3::new(-1.0, 1.0, 0.0).normalize()
+        );
+    }
+
+    #[test]
+    fn it_computes_cap_normal() {
+        let cone = Cone {
+            closed: true,
+            ..Cone::from(ModelTransformation::new())
+        };
+        assert_relative_eq!(
+            cone.normal_at(Point3::new(0.5, -1.0, 0.0)),
+            Vector3::new(0.0, -1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_hits_closed_cone_from_below() {
+        let cone = Cone {
+            closed: true,
+            ..Cone::from(ModelTransformation::new())
+        };
+        let r = Ray {
+            origin: Point3::new(0.0, -2.0, 0.0),
+            direction: Vector3::new(0.0, 1.0, 0.0),
+        };
+        assert_relative_eq!(cone.intersection(&r).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn it_samples_points_on_the_side_and_caps() {
+        let cone = Cone {
+            minimum: -1.0,
+            maximum: 2.0,
+            closed: true,
+            ..Cone::from(ModelTransformation::new())
+        };
+        let total = 5.0 * SQRT_2 * PI + 5.0 * PI;
+        assert_relative_eq!(cone.area(), total, epsilon = 1e-12);
+        let side = 5.0 * SQRT_2 * PI / total;
+        for &(u, on_cap) in &[(0.1, false), (0.5, false), (0.6, true), (0.9, true)] {
+            let sample = cone.sample_surface(u, 0.7).unwrap();
+            assert_relative_eq!(sample.normal, cone.normal_at(sample.point), epsilon = 1e-12);
+            let p = sample.point;
+            if on_cap {
+                // Either at -1 or at 2.
+                assert_relative_eq!((p.y - 0.5).abs(), 1.5);
+                assert!(p.x.powi(2) + p.z.powi(2) < p.y.powi(2));
+            } else {
+                assert_relative_eq!(p.x.powi(2) + p.z.powi(2), p.y.powi(2), epsilon = 1e-12);
+            }
+            assert_relative_eq!(sample.pdf, 1.0 / total, epsilon = 1e-12);
+            let again = cone.surface_sample_at(&sample.point).unwrap();
+            assert_relative_eq!(again.pdf, sample.pdf, epsilon = 1e-12);
+        }
+
+        // A fifth of the side is below the apex, and three fifths of it below √2.
+        assert_relative_eq!(cone.sample_surface(side * 0.2, 0.0).unwrap().point.y, 0.0);
+        assert_relative_eq!(
+            cone.sample_surface(side * 0.6, 0.0).unwrap().point.y,
+            SQRT_2,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn it_cannot_sample_open_cones_without_a_side() {
+        let cone = Cone {
+            minimum: 0.0,
+            ..Cone::from(ModelTransformation::new())
+        };
+        assert_relative_eq!(cone.area(), 0.0);
+        assert!(cone.sample_surface(0.5, 0.5).is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use na::*;
+
+use crate::bounds::*;
+use crate::color::*;
+use crate::intersections::*;
+use crate::material::*;
+use crate::model_transformation::*;
+use crate::object_transform::*;
+use crate::ray::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// An axis-aligned cube spanning [-1, 1] on every axis in object space.
+pub struct Cube {
+    pub object_to_world_space: ObjectTransform,
+    pub material: Material,
+}
+
+impl From<ModelTransformation> for Cube {
+    fn from(t: ModelTransformation) -> Self {
+        Self {
+            object_to_world_space: t.matrix().into(),
+            material: Material::default(),
+        }
+    }
+}
+
+impl Shape for Cube {
+    fn intersection(&self, ray: &Ray) -> Option<Float> {
+        ray_cube_intersection(&self.object_to_world_space.ray_to_object_space(ray)).and_then(
+            |(a, b)| {
+                // Pick the minimum intersection not behind the ray.
+                if a >= 0.0 {
+                    Some(a)
+                } else if b >= 0.0 {
+                    Some(b)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    fn normal_at(&self, world_point: Point3f) -> Vec3f {
+        let p = self
+            .object_to_world_space
+            .point_to_object_space(&world_point);
+        self.object_to_world_space
+            .normal_to_world_space(&object_normal(&p))
+    }
+
+    fn color_at(&self, world_point: &Point3f) -> Color {
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        self.material.color.color_at(object_point)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.object_to_world_space
+            .bounds_to_world_space(&BoundingBox::from_points(&[
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, 1.0),
+            ]))
+    }
+
+    fn area(&self) -> Float {
+        CUBE_AREA * self.object_to_world_space.mean_area_scale()
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        // Pick one of the six faces, then a point on it.
+        let face = ((u * 6.0) as usize).min(5);
+        let u = u * 6.0 - face as Float;
+        let (axis, sign) = (face / 2, if face.is_multiple_of(2) { 1.0 } else { -1.0 });
+        let mut object_point = Point3::new(0.0, 0.0, 0.0);
+        object_point[axis] = sign;
+        object_point[(axis + 1) % 3] = 2.0 * u - 1.0;
+        object_point[(axis + 2) % 3] = 2.0 * v - 1.0;
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &object_normal(&object_point),
+            1.0 / CUBE_AREA,
+        ))
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &object_normal(&object_point),
+            1.0 / CUBE_AREA,
+        ))
+    }
+}
+
+/// The object space area of the cube's six 2 by 2 faces.
+const CUBE_AREA: Float = 24.0;
+
+/// The normal of the face `p` is on, which is the one along the axis with the largest
+/// magnitude.
+fn object_normal(p: &Point3f) -> Vec3f {
+    let (x, y, z) = (p.x.abs(), p.y.abs(), p.z.abs());
+    if x >= y && x >= z {
+        Vector3::new(p.x.signum(), 0.0, 0.0)
+    } else if y >= z {
+        Vector3::new(0.0, p.y.signum(), 0.0)
+    } else {
+        Vector3::new(0.0, 0.0, p.z.signum())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_only_returns_non_negative_intersection() {
+        let cube = Cube::from(ModelTransformation::new());
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert_relative_eq!(cube.intersection(&r).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn it_computes_normal_on_each_face() {
+        let cube = Cube::from(ModelTransformation::new());
+        assert_relative_eq!(
+            cube.normal_at(Point3::new(1.0, 0.5, -0.8)),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+        assert_relative_eq!(
+            cube.normal_at(Point3::new(-0.4, -1.0, -0.8)),
+            Vector3::new(0.0, -1.0, 0.0)
+        );
+        assert_relative_eq!(
+            cube.normal_at(Point3::new(-0.6, 0.3, 1.0)),
+            Vector3::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn it_computes_normal_at_corner() {
+        let cube = Cube::from(ModelTransformation::new());
+        assert_relative_eq!(
+            cube.normal_at(Point3::new(-1.0, -1.0, -1.0)),
+            Vector3::new(-1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_computes_normal_for_rotated_cube() {
+        let cube = Cube::from(ModelTransformation::new().rotate_y(FRAC_PI_4));
+        let k = FRAC_PI_4.sin();
+        assert_relative_eq!(
+            cube.normal_at(Point3::new(k, 0.0, -k)),
+            Vector3::new(k, 0.0, -k),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn it_samples_points_on_every_face() {
+        let cube = Cube::from(ModelTransformation::new().scale(2.0, 1.0, 1.0));
+        for face in 0..6 {
+            let u = (Float::from(face) + 0.5) / 6.0;
+            let sample = cube.sample_surface(u, 0.25).unwrap();
+            assert_relative_eq!(sample.normal, cube.normal_at(sample.point));
+            let object_point = Point3::new(sample.point.x / 2.0, sample.point.y, sample.point.z);
+            assert_relative_eq!(object_point.coords.amax(), 1.0);
+            // The faces along x keep their size while the others double.
+            let expected = if sample.normal.x == 0.0 { 48.0 } else { 24.0 };
+            assert_relative_eq!(sample.pdf, 1.0 / expected, epsilon = 1e-12);
+            let again = cube.surface_sample_at(&sample.point).unwrap();
+            assert_relative_eq!(again.pdf, sample.pdf, epsilon = 1e-12);
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::f64::consts::*;
+
+use na::*;
+
+use crate::bounds::*;
+use crate::color::*;
+use crate::intersections::*;
+use crate::material::*;
+use crate::model_transformation::*;
+use crate::object_transform::*;
+use crate::ray::*;
+use crate::sampling::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// A cylinder of radius 1 about the object space y axis, spanning `minimum` to `maximum`
+/// along it. Closed cylinders have end caps.
+pub struct Cylinder {
+    pub object_to_world_space: ObjectTransform,
+    pub material: Material,
+    pub minimum: Float,
+    pub maximum: Float,
+    pub closed: bool,
+}
+
+impl From<ModelTransformation> for Cylinder {
+    fn from(t: ModelTransformation) -> Self {
+        Self {
+            object_to_world_space: t.matrix().into(),
+            material: Material::default(),
+            minimum: -1.0,
+            maximum: 1.0,
+            closed: false,
+        }
+    }
+}
+
+impl Cylinder {
+    /// The object space areas of the side and of the bottom and top caps, or None if the
+    /// cylinder has no area or goes on forever.
+    fn object_areas(&self) -> Option<[Float; 3]> {
+        let cap = if self.closed { PI } else { 0.0 };
+        let areas = [2.0 * PI * (self.maximum - self.minimum), cap, cap];
+        let total: Float = areas.iter().sum();
+        if total.is_finite() && total > 0.0 {
+            Some(areas)
+        } else {
+            None
+        }
+    }
+
+    fn object_normal(&self, p: &Point3f) -> Vec3f {
+        let distance = p.x.powi(2) + p.z.powi(2);
+        if distance < 1.0 && p.y >= self.maximum - BIAS {
+            Vector3::y()
+        } else if distance < 1.0 && p.y <= self.minimum + BIAS {
+            -Vector3::y()
+        } else {
+            Vector3::new(p.x, 0.0, p.z)
+        }
+    }
+}
+
+impl Shape for Cylinder {
+    fn intersection(&self, ray: &Ray) -> Option<Float> {
+        ray_cylinder_intersection(
+            &self.object_to_world_space.ray_to_object_space(ray),
+            self.minimum,
+            self.maximum,
+            self.closed,
+        )
+        .into_iter()
+        .find(|t| *t >= 0.0)
+    }
+
+    fn normal_at(&self, world_point: Point3f) -> Vec3f {
+        let p = self
+            .object_to_world_space
+            .point_to_object_space(&world_point);
+        self.object_to_world_space
+            .normal_to_world_space(&self.object_normal(&p))
+    }
+
+    fn color_at(&self, world_point: &Point3f) -> Color {
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        self.material.color.color_at(object_point)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.object_to_world_space
+            .bounds_to_world_space(&BoundingBox::from_points(&[
+                Point3::new(-1.0, self.minimum, -1.0),
+                Point3::new(1.0, self.maximum, 1.0),
+            ]))
+    }
+
+    fn area(&self) -> Float {
+        self.object_areas().map_or(0.0, |areas| {
+            areas.iter().sum::<Float>() * self.object_to_world_space.mean_area_scale()
+        })
+    }
+
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        // Pick the side or a cap in proportion to its area, then a point on it.
+        let areas = self.object_areas()?;
+        let (part, u) = Distribution::new(&areas).sample(u);
+        let (object_point, object_normal) = if part == 0 {
+            let angle = 2.0 * PI * v;
+            let height = self.minimum + u * (self.maximum - self.minimum);
+            let normal = Vector3::new(angle.cos(), 0.0, angle.sin());
+            (Point3::new(normal.x, height, normal.z), normal)
+        } else {
+            let disk = concentric_disk(u, v);
+            let (height, normal) = if part == 1 {
+                (self.minimum, -Vector3::y())
+            } else {
+                (self.maximum, Vector3::y())
+            };
+            (Point3::new(disk.0, height, disk.1), normal)
+        };
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &object_normal,
+            1.0 / areas.iter().sum::<Float>(),
+        ))
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        let areas = self.object_areas()?;
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &object_point,
+            &self.object_normal(&object_point).normalize(),
+            1.0 / areas.iter().sum::<Float>(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_computes_side_normal() {
+        let cylinder = Cylinder::from(ModelTransformation::new());
+        assert_relative_eq!(
+            cylinder.normal_at(Point3::new(1.0, 0.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+        assert_relative_eq!(
+            cylinder.normal_at(Point3::new(0.0, 0.5, -1.0)),
+            Vector3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn it_computes_cap_normals() {
+        let cylinder = Cylinder {
+            closed: true,
+            ..Cylinder::from(ModelTransformation::new())
+        };
+        assert_relative_eq!(
+            cylinder.normal_at(Point3::new(0.5, -1.0, 0.0)),
+            Vector3::new(0.0, -1.0, 0.0)
+        );
+        assert_relative_eq!(
+            cylinder.normal_at(Point3::new(0.0, 1.0, 0.5)),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_only_returns_non_negative_intersection() {
+        let cylinder = Cylinder {
+            closed: true,
+            ..Cylinder::from(ModelTransformation::new())
+        };
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::new(0.0, 1.0, 0.0),
+        };
+        assert_relative_eq!(cylinder.intersection(&r).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn it_samples_points_on_the_side_and_caps() {
+        let cylinder = Cylinder {
+            closed: true,
+            ..Cylinder::from(ModelTransformation::new())
+        };
+        // The side is twice the size of both caps together.
+        assert_relative_eq!(cylinder.area(), 6.0 * PI, epsilon = 1e-12);
+        for &(u, on_cap) in &[(0.3, false), (0.6, false), (0.75, true), (0.95, true)] {
+            let sample = cylinder.sample_surface(u, 0.4).unwrap();
+            assert_relative_eq!(sample.normal, cylinder.normal_at(sample.point));
+            let p = sample.point;
+            if on_cap {
+                assert_relative_eq!(p.y.abs(), 1.0);
+                assert!(p.x.powi(2) + p.z.powi(2) < 1.0);
+            } else {
+                assert_relative_eq!(p.x.powi(2) + p.z.powi(2), 1.0, epsilon = 1e-12);
+                assert!(p.y.abs() < 1.0);
+            }
+            assert_relative_eq!(sample.pdf, 1.0 / (6.0 * PI), epsilon = 1e-12);
+            let again = cylinder.surface_sample_at(&sample.point).unwrap();
+            assert_relative_eq!(again.pdf, sample.pdf, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn it_cannot_sample_infinite_cylinders() {
+        let cylinder = Cylinder {
+            minimum: -Float::INFINITY,
+            ..Cylinder::from(ModelTransformation::new())
+        };
+        assert_relative_eq!(cylinder.area(), 0.0);
+        assert!(cylinder.sample_surface(0.5, 0.5).is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::color::*;
+use crate::light::*;
+use crate::rng::*;
+use crate::types::*;
+
+/// Light from so far away that its rays are parallel, like sunlight. `direction` is the
+/// way the light travels.
+pub struct DirectionalLight {
+    pub color: Color,
+    pub direction: Vec3f,
+    /// Irradiance on a surface facing the light in watts per square meter, scaling
+    /// `color`. It does not fall off with distance.
+    pub intensity: Float,
+}
+
+impl Light for DirectionalLight {
+    fn samples(&self, _point: &Point3f, _rng: &mut Rng) -> Vec<LightSample> {
+        // Anything along the way back toward the light casts a shadow.
+        vec![LightSample {
+            direction: -self.direction.normalize(),
+            distance: Float::INFINITY,
+            color: self.color * self.intensity,
+            pdf: Float::INFINITY,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+    use crate::material::*;
+    use crate::plane::*;
+    use crate::ray::*;
+    use crate::scene::*;
+
+    #[test]
+    fn it_casts_shadows_from_any_distance() {
+        let light = DirectionalLight {
+            color: Color::new(1.0, 1.0, 1.0),
+            direction: Vector3::new(0.0, -3.0, 0.0),
+            intensity: 1.0,
+        };
+        let samples = light.samples(&Point3::new(5.0, 0.0, 5.0), &mut Rng::new(0, 0));
+        assert_eq!(samples.len(), 1);
+        assert_relative_eq!(samples[0].direction, Vector3::y());
+
+        let mut scene = Scene::new();
+        scene
+            .objects
+            .push(Box::new(Plane::ceiling(1e6, Material::default())));
+        scene.build_bvh();
+        let shadow_ray = Ray {
+            origin: Point3::origin(),
+            direction: samples[0].direction,
+        };
+        assert!(scene.is_occluded(&shadow_ray, samples[0].distance));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::light::*;
+use crate::rng::*;
+use crate::sampling::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// Shadow rays toward a glowing shape stop this fraction of the way short of it, so that
+/// the shape doesn't hide its own light.
+const SHADOW_MARGIN: Float = 1e-9;
+
+/// The shapes in a scene whose materials give off light, so that shadow rays can be
+/// aimed at them like at lights. Each shadow ray goes to one shape picked in proportion
+/// to its area and brightness, so a mesh of many glowing triangles is sampled as a
+/// single light rather than one light per triangle.
+pub struct Emitters {
+    /// Where the glowing shapes are among the scene's objects.
+    indices: Vec<usize>,
+    /// Picks among `indices`.
+    distribution: Distribution,
+}
+
+impl Emitters {
+    pub fn new(objects: &[Box<Shape>]) -> Self {
+        let (indices, weights): (Vec<usize>, Vec<Float>) = objects
+            .iter()
+            .enumerate()
+            .map(|(index, shape)| (index, weight(shape.as_ref())))
+            .filter(|&(_, weight)| weight > 0.0)
+            .unzip();
+        Self {
+            indices,
+            distribution: Distribution::new(&weights),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Picks a point on one of the glowing shapes among `objects`, which must be the
+    /// ones the emitters were made from, to light `point` with.
+    pub fn sample(
+        &self,
+        objects: &[Box<Shape>],
+        point: &Point3f,
+        rng: &mut Rng,
+    ) -> Option<LightSample> {
+        if self.is_empty() {
+            return None;
+        }
+        let (index, u) = self.distribution.sample(rng.next_float());
+        let shape = objects[self.indices[index]].as_ref();
+        let surface = shape.sample_surface(u, rng.next_float())?;
+        let pdf = self.pdf_of_surface(shape, point, &surface);
+        if !(pdf > 0.0 && pdf.is_finite()) {
+            return None;
+        }
+        let offset = surface.point - point;
+        let distance = offset.norm();
+        Some(LightSample {
+            direction: offset / distance,
+            distance: distance * (1.0 - SHADOW_MARGIN),
+            color: shape.material().emitted() * (1.0 / pdf),
+            pdf,
+        })
+    }
+
+    /// The density, per unit solid angle, with which `sample` picks the point `hit` on
+    /// `shape` to light `point` with.
+    pub fn pdf(&self, shape: &Shape, point: &Point3f, hit: &Point3f) -> Float {
+        if self.is_empty() {
+            return 0.0;
+        }
+        shape
+            .surface_sample_at(hit)
+            .map_or(0.0, |surface| self.pdf_of_surface(shape, point, &surface))
+    }
+
+    fn pdf_of_surface(&self, shape: &Shape, point: &Point3f, surface: &SurfaceSample) -> Float {
+        let offset = surface.point - point;
+        let distance_squared = offset.norm_squared();
+        // Shapes glow on both sides, and a patch of surface seen at a slant covers a
+        // smaller solid angle.
+        let cosine = offset.dot(&surface.normal).abs() / distance_squared.sqrt();
+        let chance = weight(shape) / self.distribution.total();
+        chance * surface.pdf * distance_squared / cosine
+    }
+}
+
+/// How often a shape is picked, relative to the others.
+fn weight(shape: &Shape) -> Float {
+    shape.material().emitted().luminance().max(0.0) * shape.area()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+
+    use approx::*;
+    use na::*;
+
+    use super::*;
+    use crate::color::*;
+    use crate::material::*;
+    use crate::model_transformation::*;
+    use crate::plane::*;
+    use crate::ray::*;
+    use crate::sphere::*;
+
+    fn glowing(radiance: Float) -> Material {
+        Material {
+            emission: Color::new(1.0, 1.0, 1.0),
+            emission_strength: radiance,
+            ..Material::default()
+        }
+    }
+
+    #[test]
+    fn it_only_samples_glowing_shapes_with_an_area() {
+        let objects: Vec<Box<Shape>> = vec![
+            Box::new(Sphere::from(ModelTransformation::new())),
+            Box::new(Plane::floor(0.0, glowing(1.0))),
+        ];
+        let emitters = Emitters::new(&objects);
+        assert!(emitters.is_empty());
+        let point = Point3::new(0.0, 3.0, 0.0);
+        assert!(emitters
+            .sample(&objects, &point, &mut Rng::new(0, 0))
+            .is_none());
+    }
+
+    #[test]
+    fn it_estimates_irradiance_from_glowing_spheres() {
+        let objects: Vec<Box<Shape>> = vec![
+            Box::new(Sphere {
+                material: glowing(2.0),
+                ..Sphere::from(ModelTransformation::new().translate(0.0, 3.0, 0.0))
+            }),
+            Box::new(Sphere {
+                material: glowing(1.0),
+                ..Sphere::from(ModelTransformation::new().translate(0.0, -3.0, 0.0))
+            }),
+        ];
+        let emitters = Emitters::new(&objects);
+        let (point, normal) = (Point3::origin(), Vector3::y());
+        let mut rng = Rng::new(0, 0);
+        let mut irradiance = 0.0;
+        for _ in 0..4000 {
+            if let Some(sample) = emitters.sample(&objects, &point, &mut rng) {
+                let hit = point + sample.direction * sample.distance;
+                let shape = if hit.y > 0.0 {
+                    &objects[0]
+                } else {
+                    &objects[1]
+                };
+                assert_relative_eq!(
+                    emitters.pdf(shape.as_ref(), &point, &hit),
+                    sample.pdf,
+                    epsilon = 1e-4 * sample.pdf
+                );
+                // Points on the far side are hidden behind the near one.
+                let shadow_ray = Ray {
+                    origin: point,
+                    direction: sample.direction,
+                };
+                if shape.intersection(&shadow_ray).unwrap() >= sample.distance {
+                    irradiance += sample.color.0.x * sample.direction.dot(&normal).max(0.0);
+                }
+            }
+        }
+        // A sphere filling a cone of half angle α overhead delivers L π sin²α, and only
+        // the upper one faces the point.
+        assert_relative_eq!(irradiance / 4000.0, 2.0 * PI / 9.0, epsilon = 0.02);
+    }
+}
@@ -0,0 +1,249 @@
+use std::f64::consts::*;
+
+use na::Vector3;
+
+use crate::color::*;
+use crate::light::*;
+use crate::rng::*;
+use crate::sample_pattern::*;
+use crate::sampling::*;
+use crate::types::*;
+
+/// Light arriving from every direction out of an infinitely distant sphere, given by an
+/// equirectangular image laid out like the equirectangular camera projection: longitude
+/// across the image with straight ahead (-z) in the middle and +x a quarter of the way
+/// in, and latitude down it with straight up at the top.
+pub struct EnvironmentLight {
+    image: Image,
+    /// Scales the radiance in the image.
+    pub intensity: Float,
+    /// How far the map is turned about the vertical axis, in radians.
+    pub rotation: Float,
+    /// The number of shadow rays toward the environment from each point.
+    pub samples: u32,
+    /// Picks a row, weighted by the brightness of all of its pixels.
+    rows: Distribution,
+    /// Picks a pixel within each row, weighted by its brightness.
+    columns: Vec<Distribution>,
+}
+
+impl EnvironmentLight {
+    #[allow(clippy::cast_precision_loss)]
+    pub fn new(image: Image, intensity: Float, rotation: Float, samples: u32) -> Self {
+        let (width, height) = (image.len(), image[0].len());
+        // Rows near the poles cover less of the sphere, so they are chosen less often.
+        let columns: Vec<Distribution> = (0..height)
+            .map(|y| {
+                let sin_theta = (PI * (y as Float + 0.5) / height as Float).sin();
+                let weights: Vec<Float> = (0..width)
+                    .map(|x| image[x][y].luminance() * sin_theta)
+                    .collect();
+                Distribution::new(&weights)
+            })
+            .collect();
+        let row_weights: Vec<Float> = columns.iter().map(Distribution::total).collect();
+        Self {
+            rows: Distribution::new(&row_weights),
+            columns,
+            image,
+            intensity,
+            rotation,
+            samples,
+        }
+    }
+
+    /// The radiance arriving from `direction`, which points away from the receiver.
+    pub fn radiance(&self, direction: &Vec3f) -> Color {
+        let (u, v) = self.coordinates(direction);
+        let (x, y) = self.pixel(u, v);
+        self.image[x][y] * self.intensity
+    }
+
+    /// The probability density, per unit solid angle, of `sample` choosing `direction`.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn pdf(&self, direction: &Vec3f) -> Float {
+        let (u, v) = self.coordinates(direction);
+        let sin_theta = (PI * v).sin();
+        if self.rows.total() <= 0.0 || sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = self.pixel(u, v);
+        let pixels = (self.image.len() * self.image[0].len()) as Float;
+        let image_pdf = self.rows.probability(y) * self.columns[y].probability(x) * pixels;
+        // The image covers 2π by π radians and pixels near the poles are squeezed.
+        image_pdf / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// Chooses a direction toward the environment in proportion to its brightness, using
+    /// `(u, v)` in [0, 1)², and returns it along with its `pdf`. The map must not be black.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample(&self, (u, v): (Float, Float)) -> (Vec3f, Float) {
+        let (y, v) = self.rows.sample(v);
+        let (x, u) = self.columns[y].sample(u);
+        let (width, height) = (self.image.len() as Float, self.image[0].len() as Float);
+        let direction = self.direction((x as Float + u) / width, (y as Float + v) / height);
+        (direction, self.pdf(&direction))
+    }
+
+    /// Where `direction` lands in the image, in [0, 1)².
+    fn coordinates(&self, direction: &Vec3f) -> (Float, Float) {
+        let longitude = direction.x.atan2(-direction.z) - self.rotation;
+        let latitude = direction.y.clamp(-1.0, 1.0).asin();
+        (
+            (0.5 - longitude / (2.0 * PI)).rem_euclid(1.0),
+            0.5 - latitude / PI,
+        )
+    }
+
+    fn direction(&self, u: Float, v: Float) -> Vec3f {
+        let longitude = (0.5 - u) * 2.0 * PI + self.rotation;
+        let latitude = (0.5 - v) * PI;
+        Vector3::new(
+            longitude.sin() * latitude.cos(),
+            latitude.sin(),
+            -longitude.cos() * latitude.cos(),
+        )
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    fn pixel(&self, u: Float, v: Float) -> (usize, usize) {
+        let (width, height) = (self.image.len(), self.image[0].len());
+        (
+            ((u * width as Float) as usize).min(width - 1),
+            ((v * height as Float) as usize).min(height - 1),
+        )
+    }
+}
+
+impl Light for EnvironmentLight {
+    /// Each sample carries its share of a Monte Carlo estimate of the irradiance.
+    fn samples(&self, _point: &Point3f, rng: &mut Rng) -> Vec<LightSample> {
+        if self.rows.total() <= 0.0 {
+            return vec![];
+        }
+        let positions = SamplePattern::Jittered.generate(self.samples, rng);
+        let count = Float::from(SamplePattern::Jittered.sample_count(self.samples));
+        positions
+            .into_iter()
+            .filter_map(|position| {
+                let (direction, pdf) = self.sample(position);
+                if pdf <= 0.0 {
+                    return None;
+                }
+                Some(LightSample {
+                    direction,
+                    distance: Float::INFINITY,
+                    color: self.radiance(&direction) * (1.0 / (count * pdf)),
+                    pdf: count * pdf,
+                })
+            })
+            .collect()
+    }
+
+    /// Every ray that escapes the scene meets the environment.
+    fn hit(&self, _origin: &Point3f, direction: &Vec3f) -> Option<LightHit> {
+        let count = Float::from(SamplePattern::Jittered.sample_count(self.samples));
+        Some(LightHit {
+            distance: Float::INFINITY,
+            radiance: self.radiance(direction),
+            pdf: count * self.pdf(direction),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+    use crate::camera::*;
+
+    fn gradient_image() -> Image {
+        (0..8)
+            .map(|x| {
+                (0..4)
+                    .map(|y| Color::new(Float::from(x), Float::from(y), 1.0))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_lines_up_with_the_equirectangular_camera() {
+        let environment = EnvironmentLight::new(gradient_image(), 1.0, 0.0, 1);
+        let camera = Camera {
+            canvas_width: 8,
+            canvas_height: 4,
+            projection: Projection::Equirectangular,
+            ..Camera::default()
+        };
+        for x in 0..8 {
+            for y in 0..4 {
+                let ray = camera.ray_through(Float::from(x) + 0.5, Float::from(y) + 0.5);
+                assert_eq!(
+                    environment.radiance(&ray.direction),
+                    Color::new(Float::from(x), Float::from(y), 1.0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_rotates_about_the_vertical_axis() {
+        let environment = EnvironmentLight::new(gradient_image(), 2.0, FRAC_PI_2, 1);
+        // A quarter turn brings what was in the middle of the map round to +x.
+        let color = environment.radiance(&Vector3::new(1.0, 0.1, 0.0).normalize());
+        assert_relative_eq!(color.0, Vector3::new(8.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn it_samples_bright_pixels_more_often() {
+        let mut image = vec![vec![Color::new(0.1, 0.1, 0.1); 8]; 16];
+        image[4][3] = Color::new(100.0, 100.0, 100.0);
+        let environment = EnvironmentLight::new(image, 1.0, 0.0, 1);
+        let mut rng = Rng::new(0, 0);
+        let hits = (0..1000)
+            .filter(|_| {
+                let (direction, pdf) = environment.sample((rng.next_float(), rng.next_float()));
+                assert_relative_eq!(pdf, environment.pdf(&direction), epsilon = 1e-9);
+                assert_relative_eq!(direction.norm(), 1.0, epsilon = 1e-12);
+                environment.radiance(&direction).0.x > 1.0
+            })
+            .count();
+        assert!(hits > 900);
+    }
+
+    #[test]
+    fn it_estimates_irradiance_of_uniform_environments() {
+        // A uniform environment of radiance 1 delivers an irradiance of π.
+        let image = vec![vec![Color::new(1.0, 1.0, 1.0); 16]; 32];
+        let environment = EnvironmentLight::new(image, 1.0, 0.0, 256);
+        let samples = environment.samples(&Point3::origin(), &mut Rng::new(0, 0));
+        let normal = Vector3::y();
+        let total: Float = samples
+            .iter()
+            .map(|s| s.color.0.x * s.direction.dot(&normal).max(0.0))
+            .sum();
+        assert_relative_eq!(total / PI, 1.0, epsilon = 0.05);
+    }
+
+    #[test]
+    fn it_is_hit_with_the_density_of_its_samples() {
+        let environment = EnvironmentLight::new(gradient_image(), 1.0, 0.3, 9);
+        let point = Point3::origin();
+        for sample in environment.samples(&point, &mut Rng::new(0, 0)) {
+            let hit = environment.hit(&point, &sample.direction).unwrap();
+            assert_relative_eq!(hit.pdf, sample.pdf, epsilon = 1e-9);
+            assert_relative_eq!(
+                (hit.radiance * (1.0 / hit.pdf)).0,
+                sample.color.0,
+                epsilon = 1e-9
+            );
+        }
+    }
+}
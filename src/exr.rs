@@ -0,0 +1,205 @@
+use std::convert::TryFrom;
+
+use crate::color::*;
+use crate::zlib;
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// Version 2, single-part scanline file with short attribute names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+const PIXEL_TYPE_FLOAT: i32 = 2;
+/// Channels are stored in alphabetical order.
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExrCompression {
+    None,
+    /// zlib over blocks of 16 scanlines, the usual choice for rendered images.
+    Zip,
+}
+
+impl ExrCompression {
+    fn id(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn scanlines_per_block(self) -> usize {
+        match self {
+            ExrCompression::None => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// Encodes an Image as a scanline `OpenEXR` file with 32-bit float B, G and R channels,
+/// keeping the linear radiance as it is.
+///
+/// <https://openexr.com/en/latest/OpenEXRFileLayout.html>
+// Radiance is narrowed to the 32-bit floats the file holds.
+#[allow(clippy::ptr_arg, clippy::cast_possible_truncation)]
+pub fn encode_exr(image: &Image, compression: ExrCompression) -> Vec<u8> {
+    let width = image.len();
+    let height = image[0].len();
+
+    let mut exr = MAGIC.to_vec();
+    exr.extend(&VERSION);
+    write_header(&mut exr, width, height, compression);
+
+    let lines_per_block = compression.scanlines_per_block();
+    let blocks: Vec<Vec<u8>> = (0..height)
+        .step_by(lines_per_block)
+        .map(|first| {
+            let last = (first + lines_per_block).min(height);
+            let mut data = Vec::with_capacity((last - first) * width * 4 * CHANNELS.len());
+            for y in first..last {
+                for channel in (0..CHANNELS.len()).rev() {
+                    for col in image {
+                        data.extend(&(col[y].0[channel] as f32).to_le_bytes());
+                    }
+                }
+            }
+            let packed = match compression {
+                ExrCompression::None => data,
+                ExrCompression::Zip => zip_block(&data),
+            };
+            let mut block = to_i32(first).to_le_bytes().to_vec();
+            block.extend(&to_i32(packed.len()).to_le_bytes());
+            block.extend(packed);
+            block
+        })
+        .collect();
+
+    // The offset table gives the position of each block from the start of the file.
+    let mut offset = exr.len() + blocks.len() * 8;
+    for block in &blocks {
+        exr.extend(&(offset as u64).to_le_bytes());
+        offset += block.len();
+    }
+    exr.extend(blocks.into_iter().flatten());
+    exr
+}
+
+fn write_header(exr: &mut Vec<u8>, width: usize, height: usize, compression: ExrCompression) {
+    let mut channels = vec![];
+    for name in &CHANNELS {
+        channels.extend(name.as_bytes());
+        channels.push(0);
+        channels.extend(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // pLinear and three reserved bytes, then x and y sampling.
+        channels.extend(&[0, 0, 0, 0]);
+        channels.extend(&1i32.to_le_bytes());
+        channels.extend(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+
+    let mut window = vec![];
+    for v in &[0, 0, to_i32(width) - 1, to_i32(height) - 1] {
+        window.extend(&v.to_le_bytes());
+    }
+
+    write_attribute(exr, "channels", "chlist", &channels);
+    write_attribute(exr, "compression", "compression", &[compression.id()]);
+    write_attribute(exr, "dataWindow", "box2i", &window);
+    write_attribute(exr, "displayWindow", "box2i", &window);
+    // Increasing y, top to bottom.
+    write_attribute(exr, "lineOrder", "lineOrder", &[0]);
+    write_attribute(exr, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    write_attribute(exr, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(exr, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    exr.push(0);
+}
+
+fn write_attribute(exr: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    exr.extend(name.as_bytes());
+    exr.push(0);
+    exr.extend(kind.as_bytes());
+    exr.push(0);
+    exr.extend(&to_i32(value.len()).to_le_bytes());
+    exr.extend(value);
+}
+
+/// Sizes and positions are stored as 32-bit signed integers.
+fn to_i32(n: usize) -> i32 {
+    i32::try_from(n).expect("EXR sizes fit in 32 bits")
+}
+
+/// Splits the bytes into even and odd halves and delta encodes them before compressing,
+/// which lines up the similar high bytes of neighbouring floats. Blocks that don't shrink
+/// are stored as they are, which readers recognise by their size.
+fn zip_block(data: &[u8]) -> Vec<u8> {
+    let mut predicted: Vec<u8> = data
+        .iter()
+        .step_by(2)
+        .chain(data.iter().skip(1).step_by(2))
+        .copied()
+        .collect();
+    for i in (1..predicted.len()).rev() {
+        predicted[i] = predicted[i]
+            .wrapping_sub(predicted[i - 1])
+            .wrapping_add(128);
+    }
+
+    let compressed = zlib::compress(&predicted);
+    if compressed.len() < data.len() {
+        compressed
+    } else {
+        data.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    }
+
+    #[test]
+    fn it_writes_uncompressed_scanlines() {
+        let image = vec![
+            vec![Color::new(1.0, 2.0, 3.0), Color::new(0.0, 0.0, 0.0)],
+            vec![Color::new(4.0, 5.0, 6.0), Color::new(0.0, 0.0, 0.0)],
+        ];
+        let exr = encode_exr(&image, ExrCompression::None);
+        assert_eq!(&exr[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        let header_end = find(&exr, b"screenWindowWidth\0float\0").unwrap() + 24 + 4 + 4 + 1;
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&exr[header_end..header_end + 8]);
+        let first_block = usize::try_from(u64::from_le_bytes(offset)).unwrap();
+        assert_eq!(first_block, header_end + 2 * 8);
+
+        let mut expected = 0i32.to_le_bytes().to_vec();
+        expected.extend(&24i32.to_le_bytes());
+        for v in &[3f32, 6.0, 2.0, 5.0, 1.0, 4.0] {
+            expected.extend(&v.to_le_bytes());
+        }
+        assert_eq!(&exr[first_block..first_block + 32], &expected[..]);
+    }
+
+    #[test]
+    fn it_predicts_and_interleaves_zip_blocks() {
+        // Incompressible input is stored as is.
+        assert_eq!(zip_block(&[1, 2, 3]), vec![1, 2, 3]);
+
+        let data = vec![0u8; 64];
+        let compressed = zip_block(&data);
+        assert!(compressed.len() < data.len());
+    }
+
+    #[test]
+    fn it_groups_zip_scanlines_into_blocks() {
+        let image = vec![vec![Color::new(0.5, 0.5, 0.5); 20]; 4];
+        let exr = encode_exr(&image, ExrCompression::Zip);
+        let header_end = find(&exr, b"screenWindowWidth\0float\0").unwrap() + 24 + 4 + 4 + 1;
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&exr[header_end + 8..header_end + 16]);
+        let second_block = usize::try_from(u64::from_le_bytes(offset)).unwrap();
+        assert_eq!(&exr[second_block..second_block + 4], &16i32.to_le_bytes());
+    }
+}
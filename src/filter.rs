@@ -0,0 +1,98 @@
+use crate::types::*;
+
+/// Reconstruction filters, which weight each sample by its offset from the pixel center.
+/// Filters wider than a pixel blend in some of the neighbouring pixels, trading a little
+/// sharpness for less aliasing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    /// Every sample within the pixel counts the same.
+    Box,
+    /// Weights fall off linearly to zero one pixel from the center.
+    Tent,
+    /// A Gaussian with a standard deviation of half a pixel, cut off at 1.5 pixels.
+    Gaussian,
+    /// The Mitchell-Netravali cubic with B = C = 1/3. Its negative lobes sharpen edges.
+    Mitchell,
+}
+
+const GAUSSIAN_SIGMA: Float = 0.5;
+const MITCHELL_B: Float = 1.0 / 3.0;
+const MITCHELL_C: Float = 1.0 / 3.0;
+
+impl Filter {
+    /// How far from the pixel center, in pixels, samples have any weight.
+    pub fn radius(self) -> Float {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Gaussian => 1.5,
+            Filter::Mitchell => 2.0,
+        }
+    }
+
+    /// The weight of a sample (`dx`, `dy`) pixels from the pixel center.
+    pub fn weight(self, dx: Float, dy: Float) -> Float {
+        self.weight_1d(dx) * self.weight_1d(dy)
+    }
+
+    fn weight_1d(self, d: Float) -> Float {
+        let d = d.abs();
+        let radius = self.radius();
+        if d > radius {
+            return 0.0;
+        }
+
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - d,
+            Filter::Gaussian => {
+                // Shifted down so the weight reaches zero at the radius.
+                let gaussian = |x: Float| (-x * x / (2.0 * GAUSSIAN_SIGMA * GAUSSIAN_SIGMA)).exp();
+                gaussian(d) - gaussian(radius)
+            }
+            Filter::Mitchell => {
+                let (b, c) = (MITCHELL_B, MITCHELL_C);
+                let weight = if d < 1.0 {
+                    (12.0 - 9.0 * b - 6.0 * c) * d.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * d * d
+                        + (6.0 - 2.0 * b)
+                } else {
+                    (-b - 6.0 * c) * d.powi(3)
+                        + (6.0 * b + 30.0 * c) * d * d
+                        + (-12.0 * b - 48.0 * c) * d
+                        + (8.0 * b + 24.0 * c)
+                };
+                weight / 6.0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn it_weights_within_radius() {
+        for filter in &[
+            Filter::Box,
+            Filter::Tent,
+            Filter::Gaussian,
+            Filter::Mitchell,
+        ] {
+            assert!(filter.weight(0.0, 0.0) > 0.0);
+            assert_relative_eq!(filter.weight(filter.radius() + 0.01, 0.0), 0.0);
+            assert_relative_eq!(filter.weight(0.3, -0.2), filter.weight(-0.3, 0.2));
+        }
+    }
+
+    #[test]
+    fn it_computes_filter_weights() {
+        assert_relative_eq!(Filter::Box.weight(0.4, -0.4), 1.0);
+        assert_relative_eq!(Filter::Tent.weight(0.5, 0.0), 0.5);
+        assert_relative_eq!(Filter::Gaussian.weight(1.5, 0.0), 0.0);
+        assert_relative_eq!(Filter::Mitchell.weight(0.0, 0.0), (8.0 / 9.0) * (8.0 / 9.0));
+        assert!(Filter::Mitchell.weight(1.5, 0.0) < 0.0);
+    }
+}
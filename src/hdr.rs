@@ -0,0 +1,285 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use na::Vector3;
+
+use crate::color::*;
+use crate::types::*;
+
+/// Scanlines of this width can be run-length encoded; others are written flat.
+const MIN_ENCODED_WIDTH: usize = 8;
+const MAX_ENCODED_WIDTH: usize = 0x7fff;
+const MAX_RUN: usize = 127;
+const MAX_LITERAL: usize = 128;
+
+/// Encodes an Image in the Radiance RGBE format, which keeps radiance above 1.0 with a
+/// shared 8-bit exponent per pixel.
+///
+/// <https://www.graphics.cornell.edu/~bjw/rgbe.html>
+#[allow(clippy::ptr_arg)]
+pub fn encode_hdr(image: &Image) -> Vec<u8> {
+    let width = image.len();
+    let height = image[0].len();
+
+    let mut bytes =
+        format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {height} +X {width}\n").into_bytes();
+    let mut scanline = Vec::with_capacity(width);
+    for y in 0..height {
+        scanline.clear();
+        scanline.extend(image.iter().map(|col| to_rgbe(col[y])));
+        if (MIN_ENCODED_WIDTH..=MAX_ENCODED_WIDTH).contains(&width) {
+            encode_scanline(&scanline, &mut bytes);
+        } else {
+            bytes.extend(scanline.iter().flatten());
+        }
+    }
+    bytes
+}
+
+/// Writes the run-length encoded form of a scanline, where each of the four components
+/// is stored separately.
+// Scanlines are at most 0x7fff wide, and runs and literal spans at most 128 long.
+#[allow(clippy::cast_possible_truncation)]
+fn encode_scanline(scanline: &[[u8; 4]], out: &mut Vec<u8>) {
+    out.extend(&[2, 2, (scanline.len() >> 8) as u8, scanline.len() as u8]);
+    for component in 0..4 {
+        let values: Vec<u8> = scanline.iter().map(|rgbe| rgbe[component]).collect();
+        let mut i = 0;
+        while i < values.len() {
+            let run = run_length(&values[i..]);
+            if run >= 3 {
+                out.push((128 + run) as u8);
+                out.push(values[i]);
+                i += run;
+                continue;
+            }
+
+            // Gather literals up to the start of the next worthwhile run.
+            let start = i;
+            while i < values.len() && i - start < MAX_LITERAL && run_length(&values[i..]) < 3 {
+                i += 1;
+            }
+            out.push((i - start) as u8);
+            out.extend(&values[start..i]);
+        }
+    }
+}
+
+fn run_length(values: &[u8]) -> usize {
+    values
+        .iter()
+        .take(MAX_RUN)
+        .take_while(|&&v| v == values[0])
+        .count()
+}
+
+/// Converts a color to a shared-exponent mantissa triple. Negative radiance is clamped
+/// to zero, and radiance too bright for the 8-bit exponent, infinity included, to the
+/// largest value it can hold.
+// The exponent and mantissas are clamped into range before they are cast.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn to_rgbe(color: Color) -> [u8; 4] {
+    let Color(v) = color;
+    let max = color.max_component();
+    if max.is_nan() || max < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+
+    // max = mantissa * 2^exponent with mantissa in [0.5, 1), unless it is out of range.
+    let exponent = (max.log2().floor() + 1.0).clamp(-128.0, 127.0) as i32;
+    let scale = 256.0 / Float::powi(2.0, exponent);
+    let mantissa = |c: Float| (c.max(0.0) * scale).min(255.0) as u8;
+    [
+        mantissa(v.x),
+        mantissa(v.y),
+        mantissa(v.z),
+        (exponent + 128) as u8,
+    ]
+}
+
+/// Converts a shared-exponent mantissa triple back to a color.
+pub fn from_rgbe(rgbe: [u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    let scale = Float::powi(2.0, i32::from(rgbe[3]) - 128 - 8);
+    Color(
+        Vector3::new(
+            Float::from(rgbe[0]),
+            Float::from(rgbe[1]),
+            Float::from(rgbe[2]),
+        ) * scale,
+    )
+}
+
+pub fn read_hdr(path: &Path) -> io::Result<Image> {
+    decode_hdr(&fs::read(path)?)
+}
+
+/// Decodes a Radiance RGBE image with the usual top-to-bottom, left-to-right
+/// orientation. Scanlines may be flat or use the run-length encoding `encode_hdr` writes.
+pub fn decode_hdr(bytes: &[u8]) -> io::Result<Image> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    // The header is lines of text up to a blank line, then the resolution line.
+    let mut lines: Vec<String> = Vec::new();
+    let mut position = 0;
+    while lines.len() < 2 || !lines[lines.len() - 2].is_empty() {
+        let end = bytes[position..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("truncated header"))?;
+        lines.push(String::from_utf8_lossy(&bytes[position..position + end]).into_owned());
+        position += end + 1;
+    }
+    if !lines[0].starts_with("#?") {
+        return Err(invalid("not a Radiance image"));
+    }
+    if let Some(format) = lines.iter().find_map(|line| line.strip_prefix("FORMAT=")) {
+        if format != "32-bit_rle_rgbe" {
+            return Err(invalid(&format!("unsupported format {format}")));
+        }
+    }
+    let resolution: Vec<&str> = lines[lines.len() - 1].split_whitespace().collect();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => (height.parse::<usize>(), width.parse::<usize>()),
+        _ => return Err(invalid("unsupported image orientation")),
+    };
+    let (height, width) = match (height, width) {
+        (Ok(height), Ok(width)) if height > 0 && width > 0 => (height, width),
+        _ => return Err(invalid("invalid image size")),
+    };
+
+    let mut image = vec![vec![Color::new(0.0, 0.0, 0.0); height]; width];
+    let mut scanline = vec![[0; 4]; width];
+    let mut data = &bytes[position..];
+    for y in 0..height {
+        data = decode_scanline(data, &mut scanline).ok_or_else(|| invalid("truncated image"))?;
+        for (column, rgbe) in image.iter_mut().zip(&scanline) {
+            column[y] = from_rgbe(*rgbe);
+        }
+    }
+    Ok(image)
+}
+
+/// Fills `scanline` from the start of `data`, returning what follows it.
+fn decode_scanline<'a>(data: &'a [u8], scanline: &mut [[u8; 4]]) -> Option<&'a [u8]> {
+    let width = scanline.len();
+    let encoded = (MIN_ENCODED_WIDTH..=MAX_ENCODED_WIDTH).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && usize::from(data[2]) << 8 | usize::from(data[3]) == width;
+    if !encoded {
+        let flat = data.get(..width * 4)?;
+        for (rgbe, chunk) in scanline.iter_mut().zip(flat.chunks(4)) {
+            rgbe.copy_from_slice(chunk);
+        }
+        return Some(&data[width * 4..]);
+    }
+
+    let mut i = 4;
+    for component in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let count = usize::from(*data.get(i)?);
+            if count == 0 {
+                return None;
+            }
+            if count > 128 {
+                let run = count - 128;
+                let value = *data.get(i + 1)?;
+                for rgbe in scanline.get_mut(x..x + run)? {
+                    rgbe[component] = value;
+                }
+                x += run;
+                i += 2;
+            } else {
+                let literals = data.get(i + 1..i + 1 + count)?;
+                for (rgbe, value) in scanline.get_mut(x..x + count)?.iter_mut().zip(literals) {
+                    rgbe[component] = *value;
+                }
+                x += count;
+                i += 1 + count;
+            }
+        }
+    }
+    Some(&data[i..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_to_rgbe() {
+        assert_eq!(to_rgbe(Color::new(0.0, 0.0, 0.0)), [0, 0, 0, 0]);
+        assert_eq!(to_rgbe(Color::new(1.0, 0.5, 0.25)), [128, 64, 32, 129]);
+        assert_eq!(to_rgbe(Color::new(10.0, 0.0, -1.0)), [160, 0, 0, 132]);
+    }
+
+    #[test]
+    fn it_saturates_radiance_too_bright_for_rgbe() {
+        assert_eq!(
+            to_rgbe(Color::new(Float::INFINITY, 1.0, 0.0)),
+            [255, 0, 0, 255]
+        );
+        let (above, below) = (Float::powi(2.0, 130), Float::powi(2.0, 126));
+        assert_eq!(to_rgbe(Color::new(above, below, 0.0)), [255, 128, 0, 255]);
+        assert_eq!(to_rgbe(Color::new(below, 0.0, 0.0)), [128, 0, 0, 255]);
+    }
+
+    #[test]
+    fn it_writes_header() {
+        let image = vec![vec![Color::new(1.0, 1.0, 1.0); 2]; 3];
+        let hdr = encode_hdr(&image);
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert_eq!(&hdr[..header.len()], &header[..]);
+        // Too narrow to encode, so the pixels follow as they are.
+        assert_eq!(&hdr[header.len()..], &[128, 128, 128, 129].repeat(6)[..]);
+    }
+
+    #[test]
+    fn it_run_length_encodes_scanlines() {
+        let mut scanline = vec![[1, 2, 3, 4]; 10];
+        scanline[9] = [5, 2, 3, 4];
+        let mut out = vec![];
+        encode_scanline(&scanline, &mut out);
+        assert_eq!(out, vec![2, 2, 0, 10, 137, 1, 1, 5, 138, 2, 138, 3, 138, 4]);
+    }
+
+    #[test]
+    fn it_converts_from_rgbe() {
+        for color in &[
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 0.5, 0.25),
+            Color::new(10.0, 0.0, 0.0),
+        ] {
+            assert_eq!(from_rgbe(to_rgbe(*color)), *color);
+        }
+    }
+
+    #[test]
+    fn it_reads_what_it_writes() {
+        for &width in &[3, 20] {
+            let image: Image = (0..width)
+                .map(|x| {
+                    (0..4)
+                        .map(|y| Color::new(Float::from(x / 3), Float::from(y), 0.5))
+                        .collect()
+                })
+                .collect();
+            assert_eq!(decode_hdr(&encode_hdr(&image)).unwrap(), image);
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_images() {
+        assert!(decode_hdr(b"P3\n").is_err());
+        assert!(decode_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0").is_err());
+        assert!(decode_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0").is_err());
+        assert!(decode_hdr(b"#?RADIANCE\n\n-Y 2 +X 1\n\0\0\0\0").is_err());
+        assert!(decode_hdr(b"#?RADIANCE\n\n-Y 1 +X 1\n\0\0\0\0").is_ok());
+    }
+}
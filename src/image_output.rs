@@ -1,47 +1,170 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::fs;
+use std::io;
 use std::path::Path;
 
 use crate::color::*;
+use crate::exr::*;
+use crate::hdr::*;
+use crate::png::*;
+use crate::tone_mapping::*;
 
-/// Writes an Image to a file with the Netpbm graphics format.
+/// Bits per color channel in the written file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> u8 {
+        match self {
+            BitDepth::Eight => 8,
+            BitDepth::Sixteen => 16,
+        }
+    }
+}
+
+/// How an image is written, beyond the format itself. Each setting only applies to the
+/// formats it makes sense for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputSettings {
+    /// For PPM and PNG. HDR and EXR always keep the full range of radiance.
+    pub bit_depth: BitDepth,
+    pub exr_compression: ExrCompression,
+    /// For PPM and PNG, which can only hold display-ready values.
+    pub tone_mapper: ToneMapper,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            bit_depth: BitDepth::Eight,
+            exr_compression: ExrCompression::Zip,
+            tone_mapper: ToneMapper::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Ppm,
+    Png,
+    /// Radiance RGBE.
+    Hdr,
+    Exr,
+}
+
+impl OutputFormat {
+    /// Picks the format matching the path's extension, ignoring case.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "ppm" => Some(OutputFormat::Ppm),
+            "png" => Some(OutputFormat::Png),
+            "hdr" => Some(OutputFormat::Hdr),
+            "exr" => Some(OutputFormat::Exr),
+            _ => None,
+        }
+    }
+
+    #[allow(clippy::ptr_arg)]
+    pub fn encode(self, image: &Image, settings: &OutputSettings) -> Vec<u8> {
+        match self {
+            OutputFormat::Ppm => encode_ppm(&settings.tone_mapper.apply(image), settings.bit_depth),
+            OutputFormat::Png => encode_png(&settings.tone_mapper.apply(image), settings.bit_depth),
+            OutputFormat::Hdr => encode_hdr(image),
+            OutputFormat::Exr => encode_exr(image, settings.exr_compression),
+        }
+    }
+}
+
+/// Writes an Image in the format named by the path's extension. Low dynamic range formats
+/// are tone mapped and sRGB encoded first; high dynamic range ones get linear radiance.
+#[allow(clippy::ptr_arg)]
+pub fn write_image(path: &Path, image: &Image, settings: &OutputSettings) -> io::Result<()> {
+    let format = OutputFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unknown image format, expected a .ppm, .png, .hdr or .exr extension",
+        )
+    })?;
+    fs::write(path, format.encode(image, settings))
+}
+
+/// Encodes an Image with the Netpbm graphics format.
 ///
 /// <https://en.wikipedia.org/wiki/Netpbm_format>
 ///
 /// One can use `convert` from `ImageMagick` to make this something friendlier,
-/// but Preview.app on macOS does view these just fine.
+/// but Preview.app on macOS does view these just fine. 16-bit samples are stored
+/// most significant byte first.
 #[allow(clippy::ptr_arg)]
-pub fn write_ppm(path: &Path, image: &Image) -> std::io::Result<()> {
+fn encode_ppm(image: &Image, bit_depth: BitDepth) -> Vec<u8> {
     let width = image.len();
     let height = image[0].len();
 
-    let mut file = match File::create(path) {
-        Ok(f) => f,
-        Err(x) => return Err(x),
-    };
-    let header = get_ppm_header(width, height);
-    let result = file.write_all(header.as_bytes());
-    if result.is_err() {
-        return result;
-    }
-
+    let mut bytes = get_ppm_header(width, height, bit_depth).into_bytes();
     for y in 0..height {
         for col in image.iter().take(width) {
-            let buf = col[y].to_u8_array();
-            let result = file.write_all(&buf);
-            if result.is_err() {
-                return result;
+            match bit_depth {
+                BitDepth::Eight => bytes.extend(&col[y].to_u8_array()),
+                BitDepth::Sixteen => {
+                    for channel in &col[y].to_u16_array() {
+                        bytes.extend(&channel.to_be_bytes());
+                    }
+                }
             }
         }
     }
-    Ok(())
+    bytes
 }
 
-fn get_ppm_header(width: usize, height: usize) -> String {
+fn get_ppm_header(width: usize, height: usize, bit_depth: BitDepth) -> String {
     // P6 declares this as a binary RGB color image.
     format!(
-        "P6\n{width} {height}\n255\n",
+        "P6\n{width} {height}\n{max_value}\n",
         width = width,
-        height = height
+        height = height,
+        max_value = (1u32 << bit_depth.bits()) - 1
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_picks_format_from_extension() {
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out.ppm")),
+            Some(OutputFormat::Ppm)
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("renders/out.PNG")),
+            Some(OutputFormat::Png)
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out.hdr")),
+            Some(OutputFormat::Hdr)
+        );
+        assert_eq!(
+            OutputFormat::from_path(Path::new("out.exr")),
+            Some(OutputFormat::Exr)
+        );
+        assert_eq!(OutputFormat::from_path(Path::new("out.jpg")), None);
+        assert_eq!(OutputFormat::from_path(Path::new("out")), None);
+    }
+
+    #[test]
+    fn it_encodes_ppm_at_both_depths() {
+        let image = vec![vec![Color::new(1.0, 0.5, 0.0)]];
+        assert_eq!(
+            encode_ppm(&image, BitDepth::Eight),
+            b"P6\n1 1\n255\n\xff\x80\x00".to_vec()
+        );
+        assert_eq!(
+            encode_ppm(&image, BitDepth::Sixteen),
+            b"P6\n1 1\n65535\n\xff\xff\x80\x00\x00\x00".to_vec()
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use crate::color::*;
+use crate::path_tracer::*;
+use crate::ray::*;
+use crate::rng::*;
+use crate::scene::*;
+use crate::trace::*;
+
+/// How the light arriving along each camera ray is worked out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Integrator {
+    /// Phong shading with shadows, mirror reflections and refraction. Fast enough for
+    /// previews, but light bouncing between surfaces is approximated by an ambient term.
+    Whitted,
+    /// Monte Carlo path tracing, which accounts for light bouncing between every kind of
+    /// surface but needs many samples per pixel to converge.
+    PathTracer,
+}
+
+impl Integrator {
+    pub fn radiance(self, scene: &Scene, primary_ray: &Ray, rng: &mut Rng) -> Color {
+        match self {
+            Integrator::Whitted => render_ray(scene, primary_ray, rng),
+            Integrator::PathTracer => render_path(scene, primary_ray, rng),
+        }
+    }
+}
@@ -26,9 +26,166 @@ pub fn ray_plane_intersection(ray: &Ray) -> Option<Float> {
     Some(-ray.origin.y / ray.direction.y)
 }
 
+/// Slab method: clip the ray against each pair of axis-aligned faces of the unit cube
+/// (spanning [-1, 1] on every axis) and keep the overlap.
+pub fn ray_cube_intersection(ray: &Ray) -> Option<(Float, Float)> {
+    let mut t_min = -Float::INFINITY;
+    let mut t_max = Float::INFINITY;
+    for axis in 0..3 {
+        let (near, far) = slab_intersection(ray.origin[axis], ray.direction[axis]);
+        t_min = t_min.max(near);
+        t_max = t_max.min(far);
+    }
+
+    if t_min > t_max {
+        return None;
+    }
+
+    Some((t_min, t_max))
+}
+
+fn slab_intersection(origin: Float, direction: Float) -> (Float, Float) {
+    // Dividing by a zero direction yields infinities of the correct sign, which is exactly
+    // what we want for rays parallel to the slab.
+    let near = (-1.0 - origin) / direction;
+    let far = (1.0 - origin) / direction;
+    if near > far {
+        (far, near)
+    } else {
+        (near, far)
+    }
+}
+
+/// Intersects the open cylinder of radius 1 about the y axis, truncated to
+/// (`minimum`, `maximum`), along with its end caps when `closed`. The intersections are
+/// returned in ascending order.
+pub fn ray_cylinder_intersection(
+    ray: &Ray,
+    minimum: Float,
+    maximum: Float,
+    closed: bool,
+) -> Vec<Float> {
+    let mut ts = vec![];
+    let a = ray.direction.x.powi(2) + ray.direction.z.powi(2);
+    // A ray parallel to the y axis can only ever hit the caps.
+    if a > EPSILON {
+        let b = 2.0 * (ray.origin.x * ray.direction.x + ray.origin.z * ray.direction.z);
+        let c = ray.origin.x.powi(2) + ray.origin.z.powi(2) - 1.0;
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return ts;
+        }
+
+        let d = discriminant.sqrt();
+        push_within_extents(ray, (-b - d) / (2.0 * a), minimum, maximum, &mut ts);
+        push_within_extents(ray, (-b + d) / (2.0 * a), minimum, maximum, &mut ts);
+    }
+
+    if closed {
+        push_cap(ray, minimum, 1.0, &mut ts);
+        push_cap(ray, maximum, 1.0, &mut ts);
+    }
+
+    sort_intersections(&mut ts);
+    ts
+}
+
+/// Intersects the double-napped cone x^2 + z^2 = y^2, truncated to (`minimum`, `maximum`),
+/// along with its end caps when `closed`. The intersections are returned in ascending
+/// order.
+pub fn ray_cone_intersection(
+    ray: &Ray,
+    minimum: Float,
+    maximum: Float,
+    closed: bool,
+) -> Vec<Float> {
+    let mut ts = vec![];
+    let (origin, direction) = (ray.origin, ray.direction);
+    let a = direction.x.powi(2) - direction.y.powi(2) + direction.z.powi(2);
+    let b = 2.0 * (origin.x * direction.x - origin.y * direction.y + origin.z * direction.z);
+    let c = origin.x.powi(2) - origin.y.powi(2) + origin.z.powi(2);
+    if a.abs() <= EPSILON {
+        // The ray is parallel to one of the cone's halves and can only hit the other.
+        if b.abs() > EPSILON {
+            push_within_extents(ray, -c / (2.0 * b), minimum, maximum, &mut ts);
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant >= 0.0 {
+            let d = discriminant.sqrt();
+            push_within_extents(ray, (-b - d) / (2.0 * a), minimum, maximum, &mut ts);
+            push_within_extents(ray, (-b + d) / (2.0 * a), minimum, maximum, &mut ts);
+        }
+    }
+
+    if closed {
+        push_cap(ray, minimum, minimum.abs(), &mut ts);
+        push_cap(ray, maximum, maximum.abs(), &mut ts);
+    }
+
+    sort_intersections(&mut ts);
+    ts
+}
+
+fn push_within_extents(ray: &Ray, t: Float, minimum: Float, maximum: Float, ts: &mut Vec<Float>) {
+    let y = ray.origin.y + t * ray.direction.y;
+    if minimum < y && y < maximum {
+        ts.push(t);
+    }
+}
+
+/// Pushes the intersection with the disk of `radius` lying in the plane at height `y`.
+fn push_cap(ray: &Ray, y: Float, radius: Float, ts: &mut Vec<Float>) {
+    if ray.direction.y.abs() <= EPSILON {
+        return;
+    }
+
+    let t = (y - ray.origin.y) / ray.direction.y;
+    let p = ray.point_at(t);
+    if p.x.powi(2) + p.z.powi(2) <= radius.powi(2) {
+        ts.push(t);
+    }
+}
+
+fn sort_intersections(ts: &mut [Float]) {
+    ts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// Möller–Trumbore intersection with the triangle at `p1` spanned by the edges `e1` and
+/// `e2`. Returns the ray parameter along with the barycentric coordinates (u, v) of the hit
+/// relative to the second and third vertices.
+pub fn ray_triangle_intersection(
+    ray: &Ray,
+    p1: &Point3f,
+    e1: &Vec3f,
+    e2: &Vec3f,
+) -> Option<(Float, Float, Float)> {
+    let dir_cross_e2 = ray.direction.cross(e2);
+    let determinant = e1.dot(&dir_cross_e2);
+    // The ray is parallel to the plane of the triangle.
+    if determinant.abs() <= EPSILON {
+        return None;
+    }
+
+    let f = 1.0 / determinant;
+    let p1_to_origin = ray.origin - p1;
+    let u = f * p1_to_origin.dot(&dir_cross_e2);
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let origin_cross_e1 = p1_to_origin.cross(e1);
+    let v = f * ray.direction.dot(&origin_cross_e1);
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    Some((f * e2.dot(&origin_cross_e1), u, v))
+}
+
 #[cfg(test)]
 mod tests {
-    use approx::assert_relative_eq;
+    use approx::{assert_relative_eq, relative_eq};
     use na::{Point3, Vector3};
 
     use super::*;
@@ -123,4 +280,244 @@ mod tests {
         let t = ray_plane_intersection(&r).unwrap();
         assert_relative_eq!(t, 1.0);
     }
+
+    #[test]
+    fn it_intersects_cube_faces() {
+        let cases = [
+            (Point3::new(5.0, 0.5, 0.0), Vector3::new(-1.0, 0.0, 0.0)),
+            (Point3::new(-5.0, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0)),
+            (Point3::new(0.5, 5.0, 0.0), Vector3::new(0.0, -1.0, 0.0)),
+            (Point3::new(0.5, -5.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            (Point3::new(0.5, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0)),
+            (Point3::new(0.5, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)),
+        ];
+        for (origin, direction) in &cases {
+            let (t1, t2) = ray_cube_intersection(&Ray {
+                origin: *origin,
+                direction: *direction,
+            })
+            .unwrap();
+            assert_relative_eq!(t1, 4.0);
+            assert_relative_eq!(t2, 6.0);
+        }
+    }
+
+    #[test]
+    fn it_computes_cube_intersection_from_inside_cube() {
+        let r = Ray {
+            origin: Point3::new(0.0, 0.5, 0.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let (t1, t2) = ray_cube_intersection(&r).unwrap();
+        assert_relative_eq!(t1, -1.0);
+        assert_relative_eq!(t2, 1.0);
+    }
+
+    #[test]
+    fn it_misses_cube() {
+        let cases = [
+            (
+                Point3::new(-2.0, 0.0, 0.0),
+                Vector3::new(0.2673, 0.5345, 0.8018),
+            ),
+            (
+                Point3::new(0.0, -2.0, 0.0),
+                Vector3::new(0.8018, 0.2673, 0.5345),
+            ),
+            (Point3::new(2.0, 0.0, 2.0), Vector3::new(0.0, 0.0, -1.0)),
+            (Point3::new(0.0, 2.0, 2.0), Vector3::new(0.0, -1.0, 0.0)),
+        ];
+        for (origin, direction) in &cases {
+            assert!(ray_cube_intersection(&Ray {
+                origin: *origin,
+                direction: *direction,
+            })
+            .is_none());
+        }
+    }
+
+    #[test]
+    fn it_misses_cylinder() {
+        let cases = [
+            (Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            (Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+            (Point3::new(0.0, 0.0, -5.0), Vector3::new(1.0, 1.0, 1.0)),
+        ];
+        for (origin, direction) in &cases {
+            let r = Ray {
+                origin: *origin,
+                direction: direction.normalize(),
+            };
+            assert!(ray_cylinder_intersection(&r, -10.0, 10.0, false).is_empty());
+        }
+    }
+
+    #[test]
+    fn it_computes_cylinder_intersection() {
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let ts = ray_cylinder_intersection(&r, -1.0, 1.0, false);
+        assert_eq!(ts.len(), 2);
+        assert_relative_eq!(ts[0], 4.0);
+        assert_relative_eq!(ts[1], 6.0);
+    }
+
+    #[test]
+    fn it_computes_cylinder_intersection_at_tangent() {
+        let r = Ray {
+            origin: Point3::new(1.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let ts = ray_cylinder_intersection(&r, -1.0, 1.0, false);
+        assert_eq!(ts.len(), 2);
+        assert_relative_eq!(ts[0], 5.0);
+        assert_relative_eq!(ts[1], 5.0);
+    }
+
+    #[test]
+    fn it_truncates_cylinder() {
+        // A steep ray from inside leaves through the open top without touching the wall.
+        let r = Ray {
+            origin: Point3::new(0.0, 1.5, 0.0),
+            direction: Vector3::new(0.1, 1.0, 0.0).normalize(),
+        };
+        assert!(ray_cylinder_intersection(&r, 1.0, 2.0, false).is_empty());
+        let r = Ray {
+            origin: Point3::new(0.0, 3.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert!(ray_cylinder_intersection(&r, 1.0, 2.0, false).is_empty());
+        let r = Ray {
+            origin: Point3::new(0.0, 1.5, -2.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert_eq!(ray_cylinder_intersection(&r, 1.0, 2.0, false).len(), 2);
+    }
+
+    #[test]
+    fn it_computes_cylinder_cap_intersections() {
+        let cases = [
+            (Point3::new(0.0, 3.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 2),
+            (Point3::new(0.0, 3.0, -2.0), Vector3::new(0.0, -1.0, 2.0), 2),
+            (Point3::new(0.0, 4.0, -2.0), Vector3::new(0.0, -1.0, 1.0), 2),
+            (Point3::new(0.0, 0.0, -2.0), Vector3::new(0.0, 1.0, 2.0), 2),
+            (Point3::new(0.0, -1.0, -2.0), Vector3::new(0.0, 1.0, 1.0), 2),
+        ];
+        for (origin, direction, count) in &cases {
+            let r = Ray {
+                origin: *origin,
+                direction: direction.normalize(),
+            };
+            assert_eq!(ray_cylinder_intersection(&r, 1.0, 2.0, true).len(), *count);
+        }
+    }
+
+    #[test]
+    fn it_computes_cone_intersection() {
+        let cases = [
+            (
+                Point3::new(0.0, 0.0, -5.0),
+                Vector3::new(0.0, 0.0, 1.0),
+                5.0,
+                5.0,
+            ),
+            (
+                Point3::new(0.0, 0.0, -5.0),
+                Vector3::new(1.0, 1.0, 1.0),
+                8.660254037844386,
+                8.660254037844386,
+            ),
+            (
+                Point3::new(1.0, 1.0, -5.0),
+                Vector3::new(-0.5, -1.0, 1.0),
+                4.550055679356349,
+                49.449944320643645,
+            ),
+        ];
+        for (origin, direction, t1, t2) in &cases {
+            let r = Ray {
+                origin: *origin,
+                direction: direction.normalize(),
+            };
+            let ts = ray_cone_intersection(&r, -Float::INFINITY, Float::INFINITY, false);
+            assert_eq!(ts.len(), 2);
+            assert!(relative_eq!(ts[0], *t1, epsilon = 1e-9));
+            assert!(relative_eq!(ts[1], *t2, epsilon = 1e-9));
+        }
+    }
+
+    #[test]
+    fn it_computes_cone_intersection_parallel_to_half() {
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -1.0),
+            direction: Vector3::new(0.0, 1.0, 1.0).normalize(),
+        };
+        let ts = ray_cone_intersection(&r, -Float::INFINITY, Float::INFINITY, false);
+        assert_eq!(ts.len(), 1);
+        assert!(relative_eq!(ts[0], 0.3535533905932738, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn it_computes_cone_cap_intersections() {
+        let cases = [
+            (Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 1.0, 0.0), 0),
+            (Point3::new(0.0, 0.0, -0.25), Vector3::new(0.0, 1.0, 1.0), 2),
+            (Point3::new(0.0, 0.0, -0.25), Vector3::new(0.0, 1.0, 0.0), 4),
+        ];
+        for (origin, direction, count) in &cases {
+            let r = Ray {
+                origin: *origin,
+                direction: direction.normalize(),
+            };
+            assert_eq!(ray_cone_intersection(&r, -0.5, 0.5, true).len(), *count);
+        }
+    }
+
+    fn triangle_edges() -> (Point3f, Vec3f, Vec3f) {
+        let p1 = Point3::new(0.0, 1.0, 0.0);
+        let p2 = Point3::new(-1.0, 0.0, 0.0);
+        let p3 = Point3::new(1.0, 0.0, 0.0);
+        (p1, p2 - p1, p3 - p1)
+    }
+
+    #[test]
+    fn it_misses_triangle_when_parallel() {
+        let (p1, e1, e2) = triangle_edges();
+        let r = Ray {
+            origin: Point3::new(0.0, -1.0, -2.0),
+            direction: Vector3::new(0.0, 1.0, 0.0),
+        };
+        assert!(ray_triangle_intersection(&r, &p1, &e1, &e2).is_none());
+    }
+
+    #[test]
+    fn it_misses_triangle_edges() {
+        let (p1, e1, e2) = triangle_edges();
+        for origin in &[
+            Point3::new(1.0, 1.0, -2.0),
+            Point3::new(-1.0, 1.0, -2.0),
+            Point3::new(0.0, -1.0, -2.0),
+        ] {
+            let r = Ray {
+                origin: *origin,
+                direction: Vector3::new(0.0, 0.0, 1.0),
+            };
+            assert!(ray_triangle_intersection(&r, &p1, &e1, &e2).is_none());
+        }
+    }
+
+    #[test]
+    fn it_computes_triangle_intersection() {
+        let (p1, e1, e2) = triangle_edges();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.5, -2.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let (t, u, v) = ray_triangle_intersection(&r, &p1, &e1, &e2).unwrap();
+        assert_relative_eq!(t, 2.0);
+        assert_relative_eq!(u, 0.25);
+        assert_relative_eq!(v, 0.25);
+    }
 }
@@ -0,0 +1,131 @@
+use crate::color::*;
+use crate::rng::*;
+use crate::types::*;
+
+/// One shadow ray's worth of light arriving at a point.
+pub struct LightSample {
+    /// Unit vector from the point being lit toward the sampled point on the light.
+    pub direction: Vec3f,
+    /// How far the shadow ray has to travel unobstructed.
+    pub distance: Float,
+    /// The irradiance this sample delivers to a surface facing the light, tinted by the
+    /// light's color and already attenuated. The samples for a point add up to the
+    /// light's full contribution.
+    pub color: Color,
+    /// The density, per unit solid angle, of the light's samples around `direction`,
+    /// summed over all of the samples it takes. Infinite for lights that rays can never
+    /// find by chance, such as point lights.
+    pub pdf: Float,
+}
+
+/// Where a ray runs into a light that has an area.
+pub struct LightHit {
+    pub distance: Float,
+    /// The radiance leaving the light toward the ray's origin. A sample in the same
+    /// direction would carry this radiance divided by `pdf`.
+    pub radiance: Color,
+    /// The density of the light's samples in the ray's direction, like
+    /// `LightSample::pdf`.
+    pub pdf: Float,
+}
+
+pub trait Light: Send + Sync {
+    /// Chooses the points on the light that illuminate `point`. Lights with an area
+    /// return several samples so that partially hidden lights cast soft shadows.
+    fn samples(&self, point: &Point3f, rng: &mut Rng) -> Vec<LightSample>;
+
+    /// Finds where a ray leaving `origin` in `direction` would meet the light, if it
+    /// can. Lights without an area can't be found this way.
+    fn hit(&self, _origin: &Point3f, _direction: &Vec3f) -> Option<LightHit> {
+        None
+    }
+}
+
+/// Veach's power heuristic for weighting a sample taken with density `pdf` against
+/// another strategy that would have taken it with density `other_pdf`. Both densities
+/// should be summed over all samples each strategy takes.
+pub fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    if pdf.is_infinite() {
+        return 1.0;
+    }
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b <= 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// How a light dims with distance. Its intensity is divided by
+/// `constant + linear * d + quadratic * d²` at a distance of `d` scene units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: Float,
+    pub linear: Float,
+    pub quadratic: Float,
+}
+
+impl Attenuation {
+    /// Equally bright at any distance.
+    pub const NONE: Self = Self {
+        constant: 1.0,
+        linear: 0.0,
+        quadratic: 0.0,
+    };
+
+    /// The physically correct falloff of light spreading out from a point.
+    pub const INVERSE_SQUARE: Self = Self {
+        constant: 0.0,
+        linear: 0.0,
+        quadratic: 1.0,
+    };
+
+    pub fn factor(&self, distance: Float) -> Float {
+        1.0 / (self.constant + self.linear * distance + self.quadratic * distance * distance)
+    }
+
+    /// The same falloff for coefficients given per meter in a scene whose units are
+    /// `meters_per_unit` meters long.
+    pub fn in_units(self, meters_per_unit: Float) -> Self {
+        Self {
+            constant: self.constant,
+            linear: self.linear * meters_per_unit,
+            quadratic: self.quadratic * meters_per_unit * meters_per_unit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+
+    use super::*;
+
+    #[test]
+    fn it_attenuates_with_distance() {
+        assert_relative_eq!(Attenuation::NONE.factor(10.0), 1.0);
+        assert_relative_eq!(Attenuation::INVERSE_SQUARE.factor(2.0), 0.25);
+        let attenuation = Attenuation {
+            constant: 1.0,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        assert_relative_eq!(attenuation.factor(2.0), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn it_weights_strategies_with_the_power_heuristic() {
+        assert_relative_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_relative_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_relative_eq!(power_heuristic(1.0, 3.0) + power_heuristic(3.0, 1.0), 1.0);
+        assert_relative_eq!(power_heuristic(Float::INFINITY, 5.0), 1.0);
+        assert_relative_eq!(power_heuristic(5.0, Float::INFINITY), 0.0);
+    }
+
+    #[test]
+    fn it_converts_attenuation_between_units() {
+        // A light 200 centimeters away is as dim as one 2 meters away.
+        let centimeters = Attenuation::INVERSE_SQUARE.in_units(0.01);
+        assert_relative_eq!(centimeters.factor(200.0), 0.25);
+    }
+}
@@ -4,128 +4,139 @@
 extern crate derive_more;
 extern crate nalgebra as na;
 
+mod area_light;
+mod bounds;
+mod bsdf;
+mod bvh;
 mod camera;
+mod cli;
 mod color;
+mod cone;
+mod cube;
+mod cylinder;
+mod directional_light;
+mod emitters;
+mod environment_light;
+mod exr;
+mod filter;
+mod hdr;
 mod image_output;
+mod integrator;
 mod intersections;
+mod light;
 mod material;
 mod model_transformation;
+mod obj_file;
+mod object_transform;
+mod path_tracer;
 mod pattern;
 mod plane;
+mod png;
 mod point_light;
 mod ray;
+mod renderer;
+mod rng;
+mod sample_pattern;
+mod sampling;
 mod scene;
+mod scene_file;
 mod shape;
 mod sphere;
+mod spot_light;
+mod tone_mapping;
 mod trace;
+mod triangle;
 mod types;
 mod view_transformation;
+mod zlib;
 
-use std::f64::consts::*;
+use std::env;
 use std::path::Path;
+use std::process;
 
-use na::*;
-
-use crate::camera::*;
+use crate::cli::*;
 use crate::color::*;
 use crate::image_output::*;
-use crate::material::*;
-use crate::model_transformation::*;
-use crate::pattern::*;
-use crate::plane::*;
-use crate::point_light::*;
-use crate::scene::*;
-use crate::sphere::*;
-use crate::trace::*;
-use crate::types::*;
-use crate::view_transformation::*;
-
-fn make_standard_material(r: Float, g: Float, b: Float) -> Material {
-    Material {
-        color: Box::new(SolidPattern(Color::new(r, g, b))),
-        ..Material::default()
-    }
-}
+use crate::renderer::*;
+use crate::scene_file::*;
+use crate::tone_mapping::*;
 
 fn main() {
-    let mut scene = Scene {
-        objects: vec![],
-        lights: vec![],
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(Command::Render(options)) => options,
+        Ok(Command::Help) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error: {e}\n\n{USAGE}");
+            process::exit(exit_code::USAGE);
+        }
     };
 
-    scene.objects.push(Box::new(Plane::floor(
-        -3.0,
-        make_standard_material(0.454902, 0.72549, 1.0),
-    )));
-    scene.objects.push(Box::new(Plane::left_wall(
-        -3.0,
-        make_standard_material(0.0, 0.721569, 0.580392),
-    )));
-    scene.objects.push(Box::new(Plane::right_wall(
-        3.0,
-        make_standard_material(1.0, 0.917647, 0.654902),
-    )));
-    scene.objects.push(Box::new(Plane::back_wall(
-        4.0,
-        Material {
-            color: Box::new(CheckersPattern {
-                a: Color::new(1.0, 1.0, 1.0),
-                b: Color::new(0.0, 0.0, 0.0),
-                object_to_pattern_space: Projective3::identity(),
-            }),
-            ..Material::default()
-        },
-    )));
-    scene.objects.push(Box::new(Plane::ceiling(
-        3.0,
-        make_standard_material(0.882353, 0.439216, 0.333333),
-    )));
-
-    scene.objects.push(Box::new(Sphere {
-        object_to_world_space: ModelTransformation::new()
-            .rotate_z(FRAC_PI_4)
-            .scale(0.5, 1.0, 0.5)
-            .matrix(),
-        material: Material {
-            color: Box::new(SolidPattern(Color::new(1.0, 1.0, 1.0))),
-            ..Material::default()
-        },
-    }));
-
-    scene.lights.push(PointLight {
-        color: Color::new(1.0, 1.0, 1.0),
-        position: Point3::new(-1.0, -1.0, -1.5),
-    });
-
-    let camera = Camera {
-        canvas_width: 500,
-        canvas_height: 400,
-        field_of_view_radians: FRAC_PI_2,
-        transform: ViewTransformation {
-            from: Point3::new(0.0, 0.0, -2.0),
-            to: Point3::new(0.0, 0.0, 0.0),
-            up: Vector3::y(),
+    let mut description = match load_scene(&options.scene_path) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Could not load {}: {}", options.scene_path.display(), e);
+            process::exit(exit_code::SCENE);
         }
-        .matrix(),
     };
-
-    let mut image: Image = Vec::with_capacity(camera.canvas_width as usize);
-    for i in 0..camera.canvas_width {
-        image.push(Vec::with_capacity(camera.canvas_height as usize));
-        for _ in 0..camera.canvas_height {
-            image[i as usize].push(Color::new(0.0, 0.0, 0.0));
+    if !options.quiet {
+        for warning in &description.warnings {
+            eprintln!("Warning: {warning}");
         }
     }
 
-    for y in 0..camera.canvas_height {
-        #[allow(clippy::needless_range_loop)]
-        for x in 0..camera.canvas_width {
-            image[x as usize][y as usize] = render(&camera, &scene, x, y);
-        }
-    }
+    let camera = &mut description.camera;
+    camera.canvas_width = options.width.unwrap_or(camera.canvas_width);
+    camera.canvas_height = options.height.unwrap_or(camera.canvas_height);
 
-    match write_ppm(Path::new("foo.ppm"), &image) {
-        Ok(_) => println!("Wrote image!"),
-        Err(e) => eprintln!("Could not write image due to:\n{:?}", e),
+    let default_renderer = Renderer::default();
+    let renderer = Renderer {
+        threads: options.threads.unwrap_or(default_renderer.threads),
+        samples_per_pixel: options.samples_per_pixel,
+        pattern: options.pattern,
+        filter: options.filter,
+        integrator: options.integrator,
+        adaptive: options.adaptive,
+        ..default_renderer
+    };
+    let (camera, scene) = (&description.camera, &description.scene);
+    let image = match &options.sample_counts_path {
+        Some(path) => {
+            let (image, sample_counts) = renderer.render_with_sample_counts(camera, scene);
+            let counts = sample_count_image(
+                &sample_counts,
+                renderer.pattern.sample_count(renderer.samples_per_pixel),
+            );
+            // The counts are data rather than light, so they are written as they are.
+            let settings = OutputSettings {
+                tone_mapper: ToneMapper {
+                    srgb: false,
+                    ..ToneMapper::default()
+                },
+                ..OutputSettings::default()
+            };
+            write_or_exit(path, &counts, &options, &settings);
+            image
+        }
+        None => renderer.render(camera, scene),
     };
+    write_or_exit(
+        &options.output_path,
+        &image,
+        &options,
+        &options.output_settings,
+    );
+}
+
+#[allow(clippy::ptr_arg)]
+fn write_or_exit(path: &Path, image: &Image, options: &Options, settings: &OutputSettings) {
+    if let Err(e) = write_image(path, image, settings) {
+        eprintln!("Could not write {}: {}", path.display(), e);
+        process::exit(exit_code::OUTPUT);
+    }
+    if !options.quiet {
+        println!("Wrote {}", path.display());
+    }
 }
@@ -1,3 +1,4 @@
+use crate::bsdf::*;
 use crate::color::*;
 use crate::pattern::*;
 use crate::types::*;
@@ -8,6 +9,39 @@ pub struct Material {
     pub diffuse: Float,
     pub specular: Float,
     pub shininess: Float,
+    /// Fraction of light mirrored off the surface, in [0, 1].
+    pub reflective: Float,
+    /// Fraction of light transmitted through the surface, in [0, 1].
+    pub transparency: Float,
+    pub refractive_index: Float,
+    /// The color of the light the surface gives off, which makes any shape a light. The
+    /// path tracer aims shadow rays at every glowing shape except an infinite plane,
+    /// cylinder or cone, whose light is only found by bounces that hit it.
+    pub emission: Color,
+    /// The radiance given off in watts per steradian per square meter, scaling
+    /// `emission`.
+    pub emission_strength: Float,
+    /// Replaces the Phong `diffuse`, `specular` and `shininess` terms with a physically
+    /// based surface whose base color is `color`.
+    pub microfacet: Option<Microfacet>,
+}
+
+/// The parameters of a `GgxBsdf`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Microfacet {
+    pub roughness: Float,
+    pub metallic: Float,
+    pub ior: Float,
+}
+
+impl Default for Microfacet {
+    fn default() -> Self {
+        Self {
+            roughness: 0.5,
+            metallic: 0.0,
+            ior: 1.5,
+        }
+    }
 }
 
 impl Default for Material {
@@ -18,6 +52,45 @@ impl Default for Material {
             diffuse: 0.9,
             specular: 0.9,
             shininess: 200.0,
+            reflective: 0.0,
+            transparency: 0.0,
+            refractive_index: 1.0,
+            emission: Color::new(0.0, 0.0, 0.0),
+            emission_strength: 1.0,
+            microfacet: None,
+        }
+    }
+}
+
+impl Material {
+    /// The radiance leaving the surface of its own accord, the same in every direction.
+    pub fn emitted(&self) -> Color {
+        self.emission * self.emission_strength
+    }
+
+    /// How the surface scatters light where its color is `color` and its normal, facing
+    /// the viewer, is `normal`. Without microfacet parameters it is a Lambertian surface
+    /// with an albedo of `diffuse` times the color under a normalized Phong highlight of
+    /// strength `specular` and exponent `shininess`, both scaled down together if they
+    /// would reflect more light than arrives.
+    pub fn bsdf(&self, color: Color, normal: Vec3f) -> Box<Bsdf> {
+        if let Some(microfacet) = self.microfacet {
+            return Box::new(GgxBsdf {
+                normal,
+                base_color: color,
+                roughness: microfacet.roughness,
+                metallic: microfacet.metallic,
+                ior: microfacet.ior,
+            });
         }
+        let diffuse = color * self.diffuse;
+        let specular = Color::new(1.0, 1.0, 1.0) * self.specular;
+        let scale = 1.0 / (diffuse + specular).max_component().max(1.0);
+        Box::new(PhongBsdf {
+            normal,
+            diffuse: diffuse * scale,
+            specular: specular * scale,
+            exponent: self.shininess,
+        })
     }
 }
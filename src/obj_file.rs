@@ -0,0 +1,323 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+use na::*;
+
+use crate::material::*;
+use crate::shape::*;
+use crate::triangle::*;
+use crate::types::*;
+
+/// One corner of a face. Indices are zero-based and already resolved against the
+/// vertices, texture coordinates and normals declared before the face.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaceVertex {
+    pub vertex: usize,
+    pub texture_coordinate: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+pub struct Group {
+    pub name: String,
+    /// Each face is a convex polygon of at least three corners.
+    pub faces: Vec<Vec<FaceVertex>>,
+}
+
+/// The subset of a Wavefront OBJ file we understand.
+///
+/// <https://en.wikipedia.org/wiki/Wavefront_.obj_file>
+pub struct ObjFile {
+    pub vertices: Vec<Point3f>,
+    pub normals: Vec<Vec3f>,
+    pub texture_coordinates: Vec<(Float, Float)>,
+    pub groups: Vec<Group>,
+    /// The number of lines that were unsupported or malformed and skipped.
+    pub ignored_lines: usize,
+}
+
+pub const DEFAULT_GROUP_NAME: &str = "default";
+
+pub fn read_obj(path: &Path) -> std::io::Result<ObjFile> {
+    fs::read_to_string(path).map(|source| parse_obj(&source))
+}
+
+/// Parses OBJ source. This never fails; anything we cannot make sense of is counted in
+/// `ignored_lines` instead.
+pub fn parse_obj(source: &str) -> ObjFile {
+    let mut obj = ObjFile {
+        vertices: vec![],
+        normals: vec![],
+        texture_coordinates: vec![],
+        groups: vec![Group {
+            name: DEFAULT_GROUP_NAME.to_string(),
+            faces: vec![],
+        }],
+        ignored_lines: 0,
+    };
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let keyword = words.next().unwrap_or("");
+        let args: Vec<&str> = words.collect();
+        let parsed = match keyword {
+            "v" => parse_floats(&args, 3).map(|v| obj.vertices.push(Point3::new(v[0], v[1], v[2]))),
+            "vn" => {
+                parse_floats(&args, 3).map(|v| obj.normals.push(Vector3::new(v[0], v[1], v[2])))
+            }
+            "vt" => parse_floats(&args, 2).map(|v| obj.texture_coordinates.push((v[0], v[1]))),
+            "f" => parse_face(&obj, &args).map(|face| {
+                if let Some(group) = obj.groups.last_mut() {
+                    group.faces.push(face);
+                }
+            }),
+            "g" => {
+                let name = if args.is_empty() {
+                    DEFAULT_GROUP_NAME.to_string()
+                } else {
+                    args.join(" ")
+                };
+                obj.groups.push(Group {
+                    name,
+                    faces: vec![],
+                });
+                Some(())
+            }
+            _ => None,
+        };
+        if parsed.is_none() {
+            obj.ignored_lines += 1;
+        }
+    }
+
+    obj.groups.retain(|group| !group.faces.is_empty());
+    obj
+}
+
+/// Parses at least `count` floats, ignoring any extras (such as the optional w of a
+/// vertex).
+fn parse_floats(args: &[&str], count: usize) -> Option<Vec<Float>> {
+    if args.len() < count {
+        return None;
+    }
+
+    args.iter()
+        .take(count)
+        .map(|arg| arg.parse().ok())
+        .collect()
+}
+
+fn parse_face(obj: &ObjFile, args: &[&str]) -> Option<Vec<FaceVertex>> {
+    if args.len() < 3 {
+        return None;
+    }
+
+    args.iter()
+        .map(|arg| {
+            // Corners are one of v, v/vt, v//vn or v/vt/vn.
+            let mut indices = arg.split('/');
+            let vertex = resolve_index(indices.next(), obj.vertices.len())?;
+            let texture_coordinate = match indices.next() {
+                None | Some("") => None,
+                index => Some(resolve_index(index, obj.texture_coordinates.len())?),
+            };
+            let normal = match indices.next() {
+                None | Some("") => None,
+                index => Some(resolve_index(index, obj.normals.len())?),
+            };
+            Some(FaceVertex {
+                vertex,
+                texture_coordinate,
+                normal,
+            })
+        })
+        .collect()
+}
+
+/// OBJ indices are one-based, and negative indices count backwards from the most recently
+/// declared element.
+fn resolve_index(index: Option<&str>, len: usize) -> Option<usize> {
+    let index: i64 = index?.parse().ok()?;
+    let resolved = if index < 0 {
+        len.checked_sub(usize::try_from(-index).ok()?)?
+    } else {
+        usize::try_from(index).ok()?.checked_sub(1)?
+    };
+    if resolved >= len {
+        return None;
+    }
+
+    Some(resolved)
+}
+
+impl ObjFile {
+    /// Fan triangulates every face into shapes ready to be added to a scene. Faces with a
+    /// normal on every corner become smooth triangles. `material_for_group` is called once
+    /// per triangle with the name of the group it belongs to.
+    pub fn triangles<F>(&self, material_for_group: F) -> Vec<Box<Shape>>
+    where
+        F: Fn(&str) -> Material,
+    {
+        let mut shapes: Vec<Box<Shape>> = vec![];
+        for group in &self.groups {
+            for face in &group.faces {
+                for i in 1..face.len() - 1 {
+                    let corners = [face[0], face[i], face[i + 1]];
+                    shapes.push(self.triangle(&corners, material_for_group(&group.name)));
+                }
+            }
+        }
+        shapes
+    }
+
+    fn triangle(&self, corners: &[FaceVertex; 3], material: Material) -> Box<Shape> {
+        let [a, b, c] = corners;
+        let vertices = [
+            self.vertices[a.vertex],
+            self.vertices[b.vertex],
+            self.vertices[c.vertex],
+        ];
+        match (a.normal, b.normal, c.normal) {
+            (Some(na), Some(nb), Some(nc)) => Box::new(SmoothTriangle::new(
+                vertices,
+                [self.normals[na], self.normals[nb], self.normals[nc]],
+                material,
+            )),
+            _ => Box::new(Triangle::new(
+                vertices[0],
+                vertices[1],
+                vertices[2],
+                material,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    #[test]
+    fn it_counts_unsupported_lines() {
+        let obj = parse_obj(
+            "There was a young lady named Bright
+who traveled much faster than light.
+mtllib scene.mtl
+v 1 2 3",
+        );
+        assert_eq!(obj.ignored_lines, 3);
+        assert_eq!(obj.vertices.len(), 1);
+    }
+
+    #[test]
+    fn it_parses_vertex_records() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1.0000 0.5000 0.0000
+vn 0 0 1
+vt 0.5 0.25",
+        );
+        assert_relative_eq!(obj.vertices[0], Point3::new(-1.0, 1.0, 0.0));
+        assert_relative_eq!(obj.vertices[1], Point3::new(-1.0, 0.5, 0.0));
+        assert_relative_eq!(obj.normals[0], Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!(obj.texture_coordinates, vec![(0.5, 0.25)]);
+    }
+
+    #[test]
+    fn it_parses_faces_in_every_corner_format() {
+        let obj = parse_obj(
+            "v 0 0 0
+v 1 0 0
+v 1 1 0
+vt 0 0
+vn 0 0 1
+f 1 2 3
+f 1/1 2/1 3/1
+f 1//1 2//1 3//1
+f 1/1/1 -2/1/1 -1/1/1",
+        );
+        assert_eq!(obj.ignored_lines, 0);
+        let faces = &obj.groups[0].faces;
+        assert_eq!(faces.len(), 4);
+        assert_eq!(
+            faces[0][1],
+            FaceVertex {
+                vertex: 1,
+                texture_coordinate: None,
+                normal: None,
+            }
+        );
+        assert_eq!(faces[1][2].texture_coordinate, Some(0));
+        assert_eq!(faces[2][0].normal, Some(0));
+        assert_eq!(faces[2][0].texture_coordinate, None);
+        assert_eq!(faces[3][2].vertex, 2);
+    }
+
+    #[test]
+    fn it_ignores_faces_with_unknown_vertices() {
+        let obj = parse_obj(
+            "v 0 0 0
+v 1 0 0
+f 1 2 3
+f 1 2",
+        );
+        assert_eq!(obj.ignored_lines, 2);
+        assert!(obj.groups.is_empty());
+    }
+
+    #[test]
+    fn it_fan_triangulates_polygons() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+f 1 2 3 4 5",
+        );
+        let triangles = obj.triangles(|_| Material::default());
+        assert_eq!(triangles.len(), 3);
+    }
+
+    #[test]
+    fn it_parses_named_groups() {
+        let obj = parse_obj(
+            "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4",
+        );
+        let names: Vec<&str> = obj.groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, vec!["FirstGroup", "SecondGroup"]);
+        assert_eq!(obj.groups[1].faces[0][2].vertex, 3);
+    }
+
+    #[test]
+    fn it_makes_smooth_triangles_from_vertex_normals() {
+        let obj = parse_obj(
+            "v 0 1 0
+v -1 0 0
+v 1 0 0
+vn -1 0 0
+vn 1 0 0
+vn 0 1 0
+f 1//3 2//1 3//2",
+        );
+        let triangles = obj.triangles(|_| Material::default());
+        assert_relative_eq!(
+            triangles[0].normal_at(Point3::new(0.0, 1.0, 0.0)),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use na::*;
+
+use crate::bounds::*;
+use crate::ray::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// An object to world space transformation along with its inverse, which would otherwise
+/// be recomputed for every ray tested against a shape.
+#[derive(Clone, Copy, Debug)]
+pub struct ObjectTransform {
+    object_to_world_space: Projective3<Float>,
+    world_to_object_space: Projective3<Float>,
+}
+
+impl From<Projective3<Float>> for ObjectTransform {
+    fn from(object_to_world_space: Projective3<Float>) -> Self {
+        Self {
+            object_to_world_space,
+            world_to_object_space: object_to_world_space.inverse(),
+        }
+    }
+}
+
+impl ObjectTransform {
+    pub fn matrix(&self) -> &Projective3<Float> {
+        &self.object_to_world_space
+    }
+
+    pub fn ray_to_object_space(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.world_to_object_space * ray.origin,
+            direction: self.world_to_object_space * ray.direction,
+        }
+    }
+
+    pub fn point_to_object_space(&self, world_point: &Point3f) -> Point3f {
+        self.world_to_object_space * world_point
+    }
+
+    /// Transforms an object space surface normal into a unit world space normal.
+    pub fn normal_to_world_space(&self, object_normal: &Vec3f) -> Vec3f {
+        // We want to invert the scaling component of the object to world space
+        // transformation applied the normal direction vector. The inverse of any rotation
+        // matrix is its transpose. Any matrix composed of rotation and scaling matrices can
+        // be orthogonally diagonalized meaning that the transpose(inv(mat)) will invert the
+        // diagonal matrix (the scaling component) and leave the rotation matrices.
+        let mut world_normal = self.world_to_object_space.to_homogeneous().transpose()
+            * Vector4::new(object_normal.x, object_normal.y, object_normal.z, 0.0);
+        // However, matrices with a translation component muck up the orthogonal
+        // diagonalization a bit, so we zero the w component of the normal before
+        // normalizing.
+        world_normal.w = 0.0;
+        world_normal.normalize().xyz()
+    }
+
+    /// How many times larger a small patch of surface with the unit object space normal
+    /// `object_normal` becomes in world space.
+    pub fn area_scale(&self, object_normal: &Vec3f) -> Float {
+        let inverse_transpose = linear_part(&self.world_to_object_space).transpose();
+        linear_part(&self.object_to_world_space).determinant().abs()
+            * (inverse_transpose * object_normal).norm()
+    }
+
+    /// Roughly how many times larger surfaces become in world space, which is exact
+    /// unless the scaling differs between axes.
+    pub fn mean_area_scale(&self) -> Float {
+        linear_part(&self.object_to_world_space)
+            .determinant()
+            .abs()
+            .powf(2.0 / 3.0)
+    }
+
+    /// Carries a point picked on a shape's surface with the object space density
+    /// `object_pdf` over to world space.
+    pub fn surface_sample_to_world_space(
+        &self,
+        object_point: &Point3f,
+        object_normal: &Vec3f,
+        object_pdf: Float,
+    ) -> SurfaceSample {
+        SurfaceSample {
+            point: self.object_to_world_space * object_point,
+            normal: self.normal_to_world_space(object_normal),
+            pdf: object_pdf / self.area_scale(object_normal),
+        }
+    }
+
+    pub fn bounds_to_world_space(&self, object_bounds: &BoundingBox) -> BoundingBox {
+        object_bounds.transform(&self.object_to_world_space)
+    }
+}
+
+/// The transformation without its translation.
+fn linear_part(transformation: &Projective3<Float>) -> Matrix3<Float> {
+    transformation
+        .matrix()
+        .fixed_slice::<U3, U3>(0, 0)
+        .into_owned()
+}
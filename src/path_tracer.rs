@@ -0,0 +1,523 @@
+use crate::bsdf::*;
+use crate::color::*;
+use crate::light::*;
+use crate::ray::*;
+use crate::rng::*;
+use crate::scene::*;
+use crate::shape::*;
+use crate::trace::*;
+use crate::types::*;
+
+/// Paths always survive this many bounces before Russian roulette may end them.
+const ROULETTE_START: u32 = 3;
+
+/// Even the brightest paths stand this chance of ending at each bounce once Russian
+/// roulette starts.
+const MIN_TERMINATION_PROBABILITY: Float = 0.05;
+
+/// Estimates the light arriving along a primary ray by following a single random path
+/// through the scene. At every surface the lights are sampled directly (next event
+/// estimation), then the path carries on in one direction chosen at random. It ends
+/// when it leaves the scene, after `max_depth` bounces, or by Russian roulette once it
+/// carries little light, which is compensated for so the estimate stays unbiased.
+///
+/// Lights with an area can be found both ways: by a shadow ray toward a point chosen on
+/// the light, or by a bounce that happens to run into it. Each estimate is weighted by
+/// the power heuristic, which favours shadow rays toward small lights and bounces off
+/// shiny surfaces where each is the better way of finding them.
+///
+/// Materials scatter light by their `Material::bsdf`, along with a perfect mirror of
+/// strength `reflective` and a perfect dielectric of strength `transparency`. `ambient`
+/// only applies to Whitted ray tracing; here indirect light comes from actual bounces
+/// instead.
+pub fn render_path(scene: &Scene, primary_ray: &Ray, rng: &mut Rng) -> Color {
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = Ray {
+        origin: primary_ray.origin,
+        direction: primary_ray.direction,
+    };
+    let mut hit = scene.intersection(&ray);
+    let mut radiance = emitted_radiance(scene, &ray, hit, None);
+    // The camera is assumed to sit in air, outside of every object.
+    let mut containers: Vec<&Shape> = vec![];
+
+    for bounce in 0..=scene.max_depth {
+        let Some((t, shape)) = hit else {
+            break;
+        };
+
+        let point = ray.point_at(t);
+        let normal = shape.normal_at(point);
+        let normal = if ray.is_inside(&normal) {
+            -normal
+        } else {
+            normal
+        };
+        let outgoing = -ray.direction;
+        let material = shape.material();
+        let bsdf = material.bsdf(shape.color_at(&point), normal);
+
+        let (n1, n2, refracted_containers) = refractive_indices(&containers, shape);
+        let (mirror, transmission) = if material.reflective > 0.0 && material.transparency > 0.0 {
+            let reflectance = schlick(&ray.direction, &normal, n1, n2);
+            (reflectance, 1.0 - reflectance)
+        } else {
+            (material.reflective, material.transparency)
+        };
+
+        // Pick one way to continue in proportion to how much light it can carry. Dividing
+        // by the chance of picking it leaves the mirror and glass weights as the total.
+        let glossy = bsdf.albedo();
+        let total = glossy + mirror + transmission;
+        let bsdf_chance = if total > 0.0 { glossy / total } else { 0.0 };
+
+        let direct = direct_radiance(
+            scene,
+            &point,
+            &normal,
+            &outgoing,
+            bsdf.as_ref(),
+            bsdf_chance,
+            rng,
+        );
+        radiance += throughput.mix(direct);
+        if total <= 0.0 {
+            break;
+        }
+
+        let choice = rng.next_float() * total;
+        let reflected = Ray {
+            origin: point + normal * BIAS,
+            direction: reflect(&ray.direction, &normal),
+        };
+        // The density of the bounce for weighting lights it runs into, or None if only
+        // the bounce could have found them.
+        let mut bsdf_pdf = None;
+        ray = if choice < glossy {
+            let Some(sample) = bsdf.sample(&outgoing, rng) else {
+                break;
+            };
+            let pdf = sample.pdf * bsdf_chance;
+            let cosine = sample.direction.dot(&normal);
+            throughput = throughput.mix(sample.value) * (cosine / pdf);
+            bsdf_pdf = Some(pdf);
+            Ray {
+                origin: point + normal * BIAS,
+                direction: sample.direction,
+            }
+        } else if choice < glossy + mirror {
+            throughput = throughput * total;
+            reflected
+        } else {
+            throughput = throughput * total;
+            match refract(&ray.direction, &normal, n1 / n2) {
+                Some(direction) => {
+                    containers = refracted_containers;
+                    Ray {
+                        origin: point - normal * BIAS,
+                        direction,
+                    }
+                }
+                // Total internal reflection.
+                None => reflected,
+            }
+        };
+
+        // Even the last bounce is followed far enough to see the lights it runs into,
+        // since the direct lighting above left part of their light for it to find.
+        hit = scene.intersection(&ray);
+        radiance += throughput.mix(emitted_radiance(scene, &ray, hit, bsdf_pdf));
+        if bounce == scene.max_depth {
+            break;
+        }
+
+        if bounce >= ROULETTE_START {
+            let termination = (1.0 - throughput.max_component()).max(MIN_TERMINATION_PROBABILITY);
+            if rng.next_float() < termination {
+                break;
+            }
+            throughput = throughput * (1.0 / (1.0 - termination));
+        }
+    }
+    radiance
+}
+
+/// The light that a ray sees coming straight from the lights before it reaches the
+/// surface it hits, or from the environment if it hits nothing, along with the light
+/// the surface gives off itself. `bsdf_pdf` is the density of the bounce that sent the
+/// ray, which weighs it against the shadow rays that could also have found the light.
+fn emitted_radiance(
+    scene: &Scene,
+    ray: &Ray,
+    hit: Option<(Float, &Shape)>,
+    bsdf_pdf: Option<Float>,
+) -> Color {
+    let weight = |light_pdf| bsdf_pdf.map_or(1.0, |pdf| power_heuristic(pdf, light_pdf));
+    let lights = scene
+        .every_light()
+        .filter_map(|light| light.hit(&ray.origin, &ray.direction))
+        .filter(|light_hit| hit.is_none_or(|(t, _)| light_hit.distance < t))
+        .fold(Color::new(0.0, 0.0, 0.0), |total, light_hit| {
+            total + light_hit.radiance * weight(light_hit.pdf)
+        });
+    match hit {
+        Some((t, shape)) if shape.material().emitted().max_component() > 0.0 => {
+            let light_pdf = scene.emitter_pdf(shape, &ray.origin, &ray.point_at(t));
+            lights + shape.material().emitted() * weight(light_pdf)
+        }
+        _ => lights,
+    }
+}
+
+/// The radiance reflected toward `outgoing` of the light reaching `point` straight from
+/// the lights, the environment and glowing objects, as found by shadow rays. `normal`
+/// faces `outgoing` and `bsdf_chance` is how often the bounce from this point follows
+/// `bsdf`.
+fn direct_radiance(
+    scene: &Scene,
+    point: &Point3f,
+    normal: &Vec3f,
+    outgoing: &Vec3f,
+    bsdf: &Bsdf,
+    bsdf_chance: Float,
+    rng: &mut Rng,
+) -> Color {
+    let mut samples: Vec<LightSample> = scene
+        .every_light()
+        .flat_map(|light| light.samples(point, rng))
+        .collect();
+    samples.extend(scene.sample_emitter(point, rng));
+
+    let mut radiance = Color::new(0.0, 0.0, 0.0);
+    for sample in samples {
+        let cosine = sample.direction.dot(normal);
+        if cosine <= 0.0 {
+            continue;
+        }
+        let shadow_ray = Ray {
+            origin: *point + normal * BIAS,
+            direction: sample.direction,
+        };
+        if !scene.is_occluded(&shadow_ray, sample.distance) {
+            let bsdf_pdf = bsdf_chance * bsdf.pdf(outgoing, &sample.direction);
+            let weight = power_heuristic(sample.pdf, bsdf_pdf);
+            let value = bsdf.eval(outgoing, &sample.direction);
+            radiance += value.mix(sample.color) * (cosine * weight);
+        }
+    }
+    radiance
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+    use std::path::Path;
+
+    use approx::*;
+    use na::*;
+
+    use super::*;
+    use crate::area_light::*;
+    use crate::environment_light::*;
+    use crate::material::*;
+    use crate::plane::*;
+    use crate::point_light::*;
+    use crate::scene_file::*;
+    use crate::sphere::*;
+
+    fn average(scene: &Scene, ray: &Ray, paths: u32) -> Color {
+        let mut rng = Rng::new(0, 0);
+        (0..paths).fold(Color::new(0.0, 0.0, 0.0), |total, _| {
+            total + render_path(scene, ray, &mut rng)
+        }) * (1.0 / Float::from(paths))
+    }
+
+    fn uniform_environment(radiance: Float) -> EnvironmentLight {
+        let image = vec![vec![Color::new(radiance, radiance, radiance); 8]; 16];
+        EnvironmentLight::new(image, 1.0, 0.0, 4)
+    }
+
+    /// One estimate each of the light reflected along `ray` straight from the lights:
+    /// by shadow rays alone, by bounces alone, and by both weighted together.
+    fn direct_estimates(scene: &Scene, ray: &Ray, rng: &mut Rng) -> [Color; 3] {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let Some((t, shape)) = scene.intersection(ray) else {
+            return [black; 3];
+        };
+        let point = ray.point_at(t);
+        let normal = shape.normal_at(point);
+        let outgoing = -ray.direction;
+        let bsdf = shape.material().bsdf(shape.color_at(&point), normal);
+
+        let mut light_only = black;
+        for light in scene.every_light() {
+            for sample in light.samples(&point, rng) {
+                let cosine = sample.direction.dot(&normal);
+                let shadow_ray = Ray {
+                    origin: point + normal * BIAS,
+                    direction: sample.direction,
+                };
+                if cosine > 0.0 && !scene.is_occluded(&shadow_ray, sample.distance) {
+                    let value = bsdf.eval(&outgoing, &sample.direction);
+                    light_only += value.mix(sample.color) * cosine;
+                }
+            }
+        }
+
+        let (mut bsdf_only, mut bounce) = (black, black);
+        if let Some(sample) = bsdf.sample(&outgoing, rng) {
+            let bounce_ray = Ray {
+                origin: point + normal * BIAS,
+                direction: sample.direction,
+            };
+            let hit = scene.intersection(&bounce_ray);
+            let scale = sample.value * (sample.direction.dot(&normal) / sample.pdf);
+            let unweighted = scene
+                .every_light()
+                .filter_map(|light| light.hit(&bounce_ray.origin, &bounce_ray.direction))
+                .filter(|light_hit| hit.is_none_or(|(t, _)| light_hit.distance < t))
+                .fold(black, |total, light_hit| total + light_hit.radiance);
+            bsdf_only = scale.mix(unweighted);
+            bounce = scale.mix(emitted_radiance(scene, &bounce_ray, hit, Some(sample.pdf)));
+        }
+        let shadow = direct_radiance(scene, &point, &normal, &outgoing, bsdf.as_ref(), 1.0, rng);
+        [light_only, bsdf_only, shadow + bounce]
+    }
+
+    #[test]
+    fn it_finds_the_veach_highlights_by_shadow_rays_bounces_and_both() {
+        let description = load_scene(Path::new("scenes/veach.scene")).unwrap();
+        let scene = &description.scene;
+        assert_eq!(scene.lights.len(), 4);
+        assert_eq!(scene.objects.len(), 5);
+
+        // The mean luminance of each kind of estimate and its standard error.
+        let estimate = |x, y| {
+            let ray = description.camera.ray_for_pixel(x, y);
+            let mut rng = Rng::new(0, 0);
+            let n = 1000;
+            let mut sums = [(0.0, 0.0); 3];
+            for _ in 0..n {
+                let estimates = direct_estimates(scene, &ray, &mut rng);
+                for (sum, estimate) in sums.iter_mut().zip(&estimates) {
+                    let l = estimate.luminance();
+                    *sum = (sum.0 + l, sum.1 + l * l);
+                }
+            }
+            let n = Float::from(n);
+            let stats = |(sum, squares): (Float, Float)| {
+                let mean = sum / n;
+                (mean, ((squares / n - mean * mean) / n).sqrt())
+            };
+            (stats(sums[0]), stats(sums[1]), stats(sums[2]))
+        };
+
+        // Shadow rays find the highlight at the first pixel far more reliably than
+        // bounces do, and bounces the one at the second. Either way the estimates agree,
+        // and weighting them together does about as well as the better one.
+        for &(x, y) in &[(108, 150), (280, 112)] {
+            let (lights, bounces, both) = estimate(x, y);
+            for &(mean, error) in &[lights, bounces] {
+                assert!((mean - both.0).abs() < 3.0 * (error + both.1));
+            }
+            let (better, worse) = if lights.1 < bounces.1 {
+                (lights.1, bounces.1)
+            } else {
+                (bounces.1, lights.1)
+            };
+            assert!(both.1 < 2.0 * better);
+            assert!(both.1 < worse / 4.0);
+        }
+    }
+
+    #[test]
+    fn it_sees_the_environment_through_mirrors() {
+        let mut scene = Scene::new();
+        scene.environment = Some(uniform_environment(0.5));
+        scene.objects.push(Box::new(Plane::floor(
+            0.0,
+            Material {
+                diffuse: 0.0,
+                specular: 0.0,
+                reflective: 1.0,
+                ..Material::default()
+            },
+        )));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+        };
+        let color = render_path(&scene, &r, &mut Rng::new(0, 0));
+        assert_relative_eq!(color.0, Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn it_reflects_all_light_in_a_white_furnace() {
+        // A white sphere under a uniform sky is exactly as bright as the sky.
+        let mut scene = Scene::new();
+        scene.environment = Some(uniform_environment(1.0));
+        scene.objects.push(Box::new(Sphere {
+            object_to_world_space: Projective3::identity().into(),
+            material: Material {
+                diffuse: 1.0,
+                specular: 0.0,
+                ..Material::default()
+            },
+        }));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.1, 1.0).normalize(),
+        };
+        let color = average(&scene, &r, 500);
+        assert_relative_eq!(color.0, Vector3::new(1.0, 1.0, 1.0), epsilon = 0.05);
+    }
+
+    #[test]
+    fn it_keeps_microfacet_materials_in_balance_in_a_white_furnace() {
+        let mut scene = Scene::new();
+        scene.environment = Some(uniform_environment(1.0));
+        scene.objects.push(Box::new(Sphere {
+            object_to_world_space: Projective3::identity().into(),
+            material: Material {
+                microfacet: Some(Microfacet {
+                    roughness: 0.2,
+                    metallic: 1.0,
+                    ..Microfacet::default()
+                }),
+                ..Material::default()
+            },
+        }));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.1, 1.0).normalize(),
+        };
+        let color = average(&scene, &r, 500);
+        assert_relative_eq!(color.0, Vector3::new(1.0, 1.0, 1.0), epsilon = 0.05);
+    }
+
+    fn matte() -> Material {
+        Material {
+            specular: 0.0,
+            ..Material::default()
+        }
+    }
+
+    #[test]
+    fn it_adds_light_bouncing_between_surfaces() {
+        let mut scene = Scene::new();
+        scene.objects.push(Box::new(Plane::floor(0.0, matte())));
+        scene.objects.push(Box::new(Plane::ceiling(2.0, matte())));
+        scene.lights.push(Box::new(PointLight {
+            position: Point3::new(0.0, 1.0, 0.0),
+            ..PointLight::default()
+        }));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+        };
+
+        scene.max_depth = 0;
+        let direct = average(&scene, &r, 1);
+        // The floor at (0, 0, 1) sees the light at 45 degrees from √2 away.
+        let k = 0.9 / PI * FRAC_PI_4.cos() / 2.0;
+        assert_relative_eq!(direct.0, Vector3::new(k, k, k), epsilon = 1e-12);
+
+        scene.max_depth = 5;
+        let global = average(&scene, &r, 200);
+        assert!(global.0.x > direct.0.x * 1.5);
+    }
+
+    #[test]
+    fn it_combines_shadow_rays_and_bounces_toward_area_lights() {
+        let mut scene = Scene::new();
+        scene.max_depth = 0;
+        scene.objects.push(Box::new(Plane::floor(0.0, matte())));
+        scene.lights.push(Box::new(SphereLight {
+            center: Point3::new(0.0, 3.0, 0.0),
+            radius: 1.0,
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 2.0,
+            attenuation: Attenuation::NONE,
+            samples: 1,
+        }));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+        };
+        // The cosine averages (1 + cos_max) / 2 over the cone of directions toward the
+        // light, whichever way they are chosen.
+        let cos_max = (1.0 - 1.0 / 9.0 as Float).sqrt();
+        let k = 0.9 / PI * 2.0 * (1.0 + cos_max) / 2.0;
+        let color = average(&scene, &r, 2000);
+        assert_relative_eq!(color.0, Vector3::new(k, k, k), epsilon = 0.01);
+
+        // The camera sees the light itself.
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::y(),
+        };
+        assert!(render_path(&scene, &r, &mut Rng::new(0, 0)).0.x > 1.0);
+    }
+
+    #[test]
+    fn it_is_lit_by_glowing_objects() {
+        let mut scene = Scene::new();
+        scene.max_depth = 0;
+        scene.objects.push(Box::new(Plane::floor(0.0, matte())));
+        scene.objects.push(Box::new(Sphere {
+            object_to_world_space: Projective3::from_matrix_unchecked(
+                Translation3::new(0.0, 3.0, 0.0).to_homogeneous(),
+            )
+            .into(),
+            material: Material {
+                diffuse: 0.0,
+                specular: 0.0,
+                emission: Color::new(1.0, 0.5, 1.0),
+                emission_strength: 2.0,
+                ..Material::default()
+            },
+        }));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+        };
+        // The sphere fills a cone of half angle α with sin α = 1/3 overhead, delivering an
+        // irradiance of L π sin²α.
+        let k = 0.9 / PI * 2.0 * PI / 9.0;
+        let color = average(&scene, &r, 2000);
+        assert_relative_eq!(color.0, Vector3::new(k, k / 2.0, k), epsilon = 0.01);
+
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::y(),
+        };
+        let color = render_path(&scene, &r, &mut Rng::new(0, 0));
+        assert_relative_eq!(color.0, Vector3::new(2.0, 1.0, 2.0));
+    }
+
+    #[test]
+    fn it_ends_paths_between_facing_mirrors() {
+        let mirror = || Material {
+            diffuse: 0.0,
+            reflective: 1.0,
+            ..Material::default()
+        };
+        let mut scene = Scene::new();
+        scene.max_depth = u32::MAX;
+        scene.objects.push(Box::new(Plane::floor(-1.0, mirror())));
+        scene.objects.push(Box::new(Plane::ceiling(1.0, mirror())));
+        scene.build_bvh();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::y(),
+        };
+        let color = render_path(&scene, &r, &mut Rng::new(0, 0));
+        assert_relative_eq!(color.0, Vector3::new(0.0, 0.0, 0.0));
+    }
+}
@@ -3,7 +3,7 @@ use na::*;
 use crate::color::*;
 use crate::types::*;
 
-pub trait Pattern {
+pub trait Pattern: Send + Sync {
     fn color_at(&self, point: Point3f) -> Color;
 }
 
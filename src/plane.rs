@@ -2,23 +2,28 @@ use std::f64::consts::*;
 
 use na::*;
 
+use crate::bounds::*;
 use crate::color::Color;
 use crate::intersections::*;
 use crate::material::*;
 use crate::model_transformation::*;
+use crate::object_transform::*;
 use crate::ray::*;
 use crate::shape::*;
 use crate::types::*;
 
 pub struct Plane {
-    pub object_to_world_space: Projective3<Float>,
+    pub object_to_world_space: ObjectTransform,
     pub material: Material,
 }
 
 impl Plane {
     pub fn floor(y: Float, mat: Material) -> Self {
         Self {
-            object_to_world_space: ModelTransformation::new().translate(0.0, y, 0.0).matrix(),
+            object_to_world_space: ModelTransformation::new()
+                .translate(0.0, y, 0.0)
+                .matrix()
+                .into(),
             material: mat,
         }
     }
@@ -28,7 +33,8 @@ impl Plane {
             object_to_world_space: ModelTransformation::new()
                 .translate(0.0, y, 0.0)
                 .rotate_z(PI)
-                .matrix(),
+                .matrix()
+                .into(),
             material: mat,
         }
     }
@@ -38,7 +44,8 @@ impl Plane {
             object_to_world_space: ModelTransformation::new()
                 .translate(x, 0.0, 0.0)
                 .rotate_z(FRAC_PI_2)
-                .matrix(),
+                .matrix()
+                .into(),
             material: mat,
         }
     }
@@ -48,7 +55,8 @@ impl Plane {
             object_to_world_space: ModelTransformation::new()
                 .translate(x, 0.0, 0.0)
                 .rotate_z(-FRAC_PI_2)
-                .matrix(),
+                .matrix()
+                .into(),
             material: mat,
         }
     }
@@ -58,7 +66,8 @@ impl Plane {
             object_to_world_space: ModelTransformation::new()
                 .translate(0.0, 0.0, z)
                 .rotate_x(-FRAC_PI_2)
-                .matrix(),
+                .matrix()
+                .into(),
             material: mat,
         }
     }
@@ -66,26 +75,33 @@ impl Plane {
 
 impl Shape for Plane {
     fn intersection(&self, ray: &Ray) -> Option<Float> {
-        let ray_transformation = self.object_to_world_space.inverse();
-        ray_plane_intersection(&Ray {
-            origin: ray_transformation * ray.origin,
-            direction: ray_transformation * ray.direction,
+        ray_plane_intersection(&self.object_to_world_space.ray_to_object_space(ray)).and_then(|t| {
+            if t >= EPSILON {
+                Some(t)
+            } else {
+                None
+            }
         })
-        .and_then(|t| if t >= EPSILON { Some(t) } else { None })
     }
 
     fn normal_at(&self, _: Point3f) -> Vec3f {
-        self.object_to_world_space * Vector3::y()
+        self.object_to_world_space.matrix() * Vector3::y()
     }
 
     fn color_at(&self, world_point: &Point3f) -> Color {
-        let object_point = self.object_to_world_space.inverse() * world_point;
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
         self.material.color.color_at(object_point)
     }
 
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::infinite()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,195 @@
+use std::convert::TryFrom;
+
+use crate::color::*;
+use crate::image_output::BitDepth;
+use crate::zlib;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const COLOR_TYPE_RGB: u8 = 2;
+
+/// Encodes an Image as a PNG file.
+///
+/// <https://www.w3.org/TR/png/>
+///
+/// Each scanline gets whichever filter leaves the smallest sum of absolute differences,
+/// the heuristic the spec recommends for true color images.
+#[allow(clippy::ptr_arg)]
+pub fn encode_png(image: &Image, bit_depth: BitDepth) -> Vec<u8> {
+    let width = image.len();
+    let height = image[0].len();
+    let bytes_per_pixel = match bit_depth {
+        BitDepth::Eight => 3,
+        BitDepth::Sixteen => 6,
+    };
+
+    let mut header = Vec::with_capacity(13);
+    let dimension = |n| u32::try_from(n).expect("PNG dimensions fit in 32 bits");
+    header.extend(&dimension(width).to_be_bytes());
+    header.extend(&dimension(height).to_be_bytes());
+    header.push(bit_depth.bits());
+    header.push(COLOR_TYPE_RGB);
+    // Compression method, filter method and interlace method: all the default.
+    header.extend(&[0, 0, 0]);
+
+    let row_length = width * bytes_per_pixel;
+    let mut filtered = Vec::with_capacity((row_length + 1) * height);
+    let mut previous = vec![0; row_length];
+    let mut row = Vec::with_capacity(row_length);
+    for y in 0..height {
+        row.clear();
+        for col in image {
+            match bit_depth {
+                BitDepth::Eight => row.extend(&col[y].to_u8_array()),
+                BitDepth::Sixteen => {
+                    for channel in &col[y].to_u16_array() {
+                        row.extend(&channel.to_be_bytes());
+                    }
+                }
+            }
+        }
+        filter_row(&row, &previous, bytes_per_pixel, &mut filtered);
+        std::mem::swap(&mut row, &mut previous);
+    }
+
+    let mut png = SIGNATURE.to_vec();
+    write_chunk(&mut png, *b"IHDR", &header);
+    write_chunk(&mut png, *b"IDAT", &zlib::compress(&filtered));
+    write_chunk(&mut png, *b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: [u8; 4], data: &[u8]) {
+    let length = u32::try_from(data.len()).expect("PNG chunks fit in 32 bits");
+    png.extend(&length.to_be_bytes());
+    let start = png.len();
+    png.extend(&kind);
+    png.extend(data);
+    let crc = zlib::crc32(&png[start..]);
+    png.extend(&crc.to_be_bytes());
+}
+
+/// Appends the filter type byte and the filtered `row` to `out`.
+fn filter_row(row: &[u8], previous: &[u8], bytes_per_pixel: usize, out: &mut Vec<u8>) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter in 0..5 {
+        let candidate: Vec<u8> = (0..row.len())
+            .map(|i| {
+                let a = if i >= bytes_per_pixel {
+                    row[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let b = previous[i];
+                let c = if i >= bytes_per_pixel {
+                    previous[i - bytes_per_pixel]
+                } else {
+                    0
+                };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => u8::midpoint(a, b),
+                    _ => paeth(a, b, c),
+                };
+                row[i].wrapping_sub(predictor)
+            })
+            .collect();
+        // Treat the bytes as signed so small negative differences count as small.
+        let cost = candidate
+            .iter()
+            .map(|&byte| u64::from(byte.cast_signed().unsigned_abs()))
+            .sum();
+        if best
+            .as_ref()
+            .is_none_or(|(best_cost, _, _)| cost < *best_cost)
+        {
+            best = Some((cost, filter, candidate));
+        }
+    }
+
+    let (_, filter, bytes) = best.expect("there is always a filter");
+    out.push(filter);
+    out.extend(bytes);
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_image(width: usize, height: usize) -> Image {
+        vec![vec![Color::new(0.5, 0.5, 0.5); height]; width]
+    }
+
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = vec![];
+        let mut i = SIGNATURE.len();
+        while i < png.len() {
+            let mut length = [0; 4];
+            length.copy_from_slice(&png[i..i + 4]);
+            let length = u32::from_be_bytes(length) as usize;
+            let kind = String::from_utf8(png[i + 4..i + 8].to_vec()).unwrap();
+            let data = png[i + 8..i + 8 + length].to_vec();
+            let mut crc = [0; 4];
+            crc.copy_from_slice(&png[i + 8 + length..i + 12 + length]);
+            assert_eq!(
+                u32::from_be_bytes(crc),
+                zlib::crc32(&png[i + 4..i + 8 + length])
+            );
+            chunks.push((kind, data));
+            i += 12 + length;
+        }
+        chunks
+    }
+
+    #[test]
+    fn it_writes_signature_and_chunks() {
+        let png = encode_png(&gray_image(3, 2), BitDepth::Eight);
+        assert_eq!(&png[..8], &SIGNATURE);
+
+        let chunks = chunks(&png);
+        let kinds: Vec<&str> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(kinds, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(
+            chunks[0].1,
+            vec![0, 0, 0, 3, 0, 0, 0, 2, 8, COLOR_TYPE_RGB, 0, 0, 0]
+        );
+        assert!(chunks[2].1.is_empty());
+    }
+
+    #[test]
+    fn it_declares_sixteen_bit_depth() {
+        let png = encode_png(&gray_image(1, 1), BitDepth::Sixteen);
+        assert_eq!(chunks(&png)[0].1[8], 16);
+    }
+
+    #[test]
+    fn it_picks_the_cheapest_filter() {
+        // A smooth gradient is constant after the Sub filter.
+        let row: Vec<u8> = (0..12).map(|i| i / 3 * 5).collect();
+        let mut out = vec![];
+        filter_row(&row, &[0; 12], 3, &mut out);
+        assert_eq!(out, vec![1, 0, 0, 0, 5, 5, 5, 5, 5, 5, 5, 5, 5]);
+    }
+
+    #[test]
+    fn it_predicts_with_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+}
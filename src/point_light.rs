@@ -1,9 +1,27 @@
+use na::Point3;
+
 use crate::color::*;
+use crate::light::*;
+use crate::rng::*;
 use crate::types::*;
 
 pub struct PointLight {
     pub color: Color,
     pub position: Point3f,
+    /// Radiant intensity in watts per steradian, scaling `color`.
+    pub intensity: Float,
+    pub attenuation: Attenuation,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0),
+            position: Point3::new(0.0, 0.0, 0.0),
+            intensity: 1.0,
+            attenuation: Attenuation::INVERSE_SQUARE,
+        }
+    }
 }
 
 impl PointLight {
@@ -12,3 +30,36 @@ impl PointLight {
         (v.norm(), v.normalize())
     }
 }
+
+impl Light for PointLight {
+    fn samples(&self, point: &Point3f, _rng: &mut Rng) -> Vec<LightSample> {
+        let (distance, direction) = self.direction_from(point);
+        vec![LightSample {
+            direction,
+            distance,
+            color: self.color * (self.intensity * self.attenuation.factor(distance)),
+            pdf: Float::INFINITY,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+
+    #[test]
+    fn it_dims_with_distance() {
+        let light = PointLight {
+            intensity: 8.0,
+            attenuation: Attenuation::INVERSE_SQUARE,
+            ..PointLight::default()
+        };
+        let near = &light.samples(&Point3::new(0.0, 1.0, 0.0), &mut Rng::new(0, 0))[0];
+        let far = &light.samples(&Point3::new(0.0, 2.0, 0.0), &mut Rng::new(0, 0))[0];
+        assert_relative_eq!(near.color.0, Vector3::new(8.0, 8.0, 8.0));
+        assert_relative_eq!(far.color.0, Vector3::new(2.0, 2.0, 2.0));
+    }
+}
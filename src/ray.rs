@@ -23,6 +23,37 @@ pub fn reflect(incoming: &Vec3f, surface_normal: &Vec3f) -> Vec3f {
     incoming - *surface_normal * 2.0 * incoming.dot(surface_normal)
 }
 
+/// Bends a unit `incoming` direction through a surface by Snell's law, where
+/// `refraction_ratio` is n1 / n2 and `surface_normal` faces against `incoming`. Returns
+/// None under total internal reflection.
+pub fn refract(incoming: &Vec3f, surface_normal: &Vec3f, refraction_ratio: Float) -> Option<Vec3f> {
+    let cos_i = -incoming.dot(surface_normal);
+    let sin2_t = refraction_ratio.powi(2) * (1.0 - cos_i.powi(2));
+    if sin2_t > 1.0 {
+        return None;
+    }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(incoming * refraction_ratio + surface_normal * (refraction_ratio * cos_i - cos_t))
+}
+
+/// Schlick's approximation of the Fresnel equations: the fraction of light reflected
+/// (rather than refracted) when passing from a medium with index `n1` into `n2`.
+pub fn schlick(incoming: &Vec3f, surface_normal: &Vec3f, n1: Float, n2: Float) -> Float {
+    let mut cos = -incoming.dot(surface_normal);
+    if n1 > n2 {
+        let sin2_t = (n1 / n2).powi(2) * (1.0 - cos.powi(2));
+        if sin2_t > 1.0 {
+            return 1.0;
+        }
+        // Going from dense to less dense media uses the angle of transmission instead.
+        cos = (1.0 - sin2_t).sqrt();
+    }
+
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
 #[cfg(test)]
 mod tests {
     use std::f64::consts::*;
@@ -58,4 +89,60 @@ mod tests {
             Vector3::new(1.0, 0.0, 0.0)
         );
     }
+
+    #[test]
+    fn it_does_not_bend_ray_with_matching_indices() {
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalize();
+        assert_relative_eq!(
+            refract(&incoming, &Vector3::new(0.0, 1.0, 0.0), 1.0).unwrap(),
+            incoming
+        );
+    }
+
+    #[test]
+    fn it_refracts_into_denser_medium() {
+        let k = FRAC_PI_4.sin();
+        let refracted = refract(
+            &Vector3::new(k, -k, 0.0),
+            &Vector3::new(0.0, 1.0, 0.0),
+            1.0 / 1.5,
+        )
+        .unwrap();
+        // Snell's law: sin(theta_t) = sin(theta_i) * n1 / n2.
+        assert_relative_eq!(refracted.x, k / 1.5);
+        assert_relative_eq!(refracted.norm(), 1.0);
+    }
+
+    #[test]
+    fn it_detects_total_internal_reflection() {
+        let k = FRAC_PI_4.sin();
+        assert!(refract(&Vector3::new(k, -k, 0.0), &Vector3::new(0.0, 1.0, 0.0), 1.5).is_none());
+    }
+
+    #[test]
+    fn it_computes_schlick_under_total_internal_reflection() {
+        let k = FRAC_PI_4.sin();
+        assert_relative_eq!(
+            schlick(
+                &Vector3::new(k, -k, 0.0),
+                &Vector3::new(0.0, 1.0, 0.0),
+                1.5,
+                1.0
+            ),
+            1.0
+        );
+    }
+
+    #[test]
+    fn it_computes_schlick_at_normal_incidence() {
+        assert_relative_eq!(
+            schlick(
+                &Vector3::new(0.0, -1.0, 0.0),
+                &Vector3::new(0.0, 1.0, 0.0),
+                1.0,
+                1.5
+            ),
+            0.04
+        );
+    }
 }
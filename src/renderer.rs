@@ -0,0 +1,447 @@
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::camera::*;
+use crate::color::*;
+use crate::filter::*;
+use crate::integrator::*;
+use crate::rng::*;
+use crate::sample_pattern::*;
+use crate::scene::*;
+use crate::types::*;
+
+/// Renders an image by splitting the canvas into square tiles which worker threads pull
+/// from a shared queue until none are left.
+pub struct Renderer {
+    pub threads: usize,
+    /// Width and height of a tile in pixels.
+    pub tile_size: u32,
+    /// Rays traced per pixel. The grid based patterns round this up to a square number.
+    pub samples_per_pixel: u32,
+    /// Where within the filter's footprint around each pixel the rays go.
+    pub pattern: SamplePattern,
+    /// How the rays are weighted to produce the pixel's color.
+    pub filter: Filter,
+    /// How the light along each ray is computed.
+    pub integrator: Integrator,
+    /// When set, pixels stop early once they have converged, so `samples_per_pixel` is
+    /// only the most any pixel gets.
+    pub adaptive: Option<AdaptiveSampling>,
+}
+
+pub const DEFAULT_MIN_SAMPLES: u32 = 4;
+
+/// Settings for spending samples where the image is noisy or has edges rather than
+/// evenly across it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    /// Samples every pixel gets before the first check for convergence, and taken between
+    /// later checks.
+    pub min_samples: u32,
+    /// A pixel has converged once the standard error of its mean luminance is at most
+    /// this.
+    pub threshold: Float,
+}
+
+/// How many samples each pixel took, indexed like an Image.
+pub type SampleCounts = Vec<Vec<u32>>;
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self {
+            threads: thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get),
+            tile_size: 16,
+            samples_per_pixel: 1,
+            pattern: SamplePattern::Grid,
+            filter: Filter::Box,
+            integrator: Integrator::Whitted,
+            adaptive: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Tile {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Renderer {
+    pub fn render(&self, camera: &Camera, scene: &Scene) -> Image {
+        self.render_with_sample_counts(camera, scene).0
+    }
+
+    pub fn render_with_sample_counts(
+        &self,
+        camera: &Camera,
+        scene: &Scene,
+    ) -> (Image, SampleCounts) {
+        let tiles = self.tiles(camera);
+        let next_tile = AtomicUsize::new(0);
+        let rendered: Vec<(Tile, Vec<(Color, u32)>)> = thread::scope(|s| {
+            let workers: Vec<_> = (0..self.threads.max(1))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut finished = vec![];
+                        while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed))
+                        {
+                            finished.push((*tile, self.render_tile(camera, scene, tile)));
+                        }
+                        finished
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("render worker panicked"))
+                .collect()
+        });
+
+        let mut image = blank_image(camera.canvas_width, camera.canvas_height);
+        let mut counts = vec![vec![0; camera.canvas_height as usize]; camera.canvas_width as usize];
+        for (tile, pixels) in rendered {
+            let mut pixels = pixels.into_iter();
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    if let Some((color, count)) = pixels.next() {
+                        image[x as usize][y as usize] = color;
+                        counts[x as usize][y as usize] = count;
+                    }
+                }
+            }
+        }
+        (image, counts)
+    }
+
+    fn tiles(&self, camera: &Camera) -> Vec<Tile> {
+        let size = self.tile_size.max(1);
+        let mut tiles = vec![];
+        for y in (0..camera.canvas_height).step_by(size as usize) {
+            for x in (0..camera.canvas_width).step_by(size as usize) {
+                tiles.push(Tile {
+                    x,
+                    y,
+                    width: size.min(camera.canvas_width - x),
+                    height: size.min(camera.canvas_height - y),
+                });
+            }
+        }
+        tiles
+    }
+
+    /// Renders a tile's pixels in row-major order.
+    fn render_tile(&self, camera: &Camera, scene: &Scene, tile: &Tile) -> Vec<(Color, u32)> {
+        let mut pixels = Vec::with_capacity((tile.width * tile.height) as usize);
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                pixels.push(self.render_pixel(camera, scene, x, y));
+            }
+        }
+        pixels
+    }
+
+    /// Spreads the samples over the whole footprint of the filter rather than just the
+    /// pixel, so each pixel can be reconstructed on its own as a weighted average. Returns
+    /// the color along with the number of samples taken.
+    fn render_pixel(&self, camera: &Camera, scene: &Scene, x: u32, y: u32) -> (Color, u32) {
+        let mut rng = Rng::for_pixel(x, y, 0);
+        let mut positions = self.pattern.generate(self.samples_per_pixel, &mut rng);
+        let batch_size = match self.adaptive {
+            Some(adaptive) => {
+                // Stopping early takes a prefix of the samples, which for a grid would
+                // only cover its first rows.
+                if !self.pattern.is_progressive() {
+                    shuffle(&mut positions, &mut rng);
+                }
+                adaptive.min_samples.max(2) as usize
+            }
+            None => positions.len(),
+        };
+
+        let radius = self.filter.radius();
+        let mut total = Color::new(0.0, 0.0, 0.0);
+        let mut total_weight = 0.0;
+        let mut luminance = RunningVariance::default();
+        for (u, v) in positions {
+            let (dx, dy) = ((2.0 * u - 1.0) * radius, (2.0 * v - 1.0) * radius);
+            let weight = self.filter.weight(dx, dy);
+            let lens = (rng.next_float(), rng.next_float());
+            let color = self.integrator.radiance(
+                scene,
+                &camera.ray_through_lens(
+                    Float::from(x) + 0.5 + dx,
+                    Float::from(y) + 0.5 + dy,
+                    lens,
+                ),
+                &mut rng,
+            );
+            total += color * weight;
+            total_weight += weight;
+            luminance.add(color.luminance());
+
+            if let Some(adaptive) = self.adaptive {
+                if luminance.count % batch_size == 0
+                    && luminance.standard_error() <= adaptive.threshold
+                {
+                    break;
+                }
+            }
+        }
+
+        // Only the negative lobes of a filter could cancel out the rest of the weights.
+        let color = if total_weight.abs() < EPSILON {
+            Color::new(0.0, 0.0, 0.0)
+        } else {
+            total * (1.0 / total_weight)
+        };
+        let count = u32::try_from(luminance.count).expect("sample counts fit in 32 bits");
+        (color, count)
+    }
+}
+
+/// Welford's online algorithm for the mean and variance of a stream of values.
+#[derive(Default)]
+struct RunningVariance {
+    count: usize,
+    mean: Float,
+    squared_deviations: Float,
+}
+
+// Sample counts are far too small to lose precision as floats.
+#[allow(clippy::cast_precision_loss)]
+impl RunningVariance {
+    fn add(&mut self, value: Float) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as Float;
+        self.squared_deviations += delta * (value - self.mean);
+    }
+
+    /// The standard error of the mean, from the unbiased sample variance.
+    fn standard_error(&self) -> Float {
+        if self.count < 2 {
+            return Float::INFINITY;
+        }
+        let n = self.count as Float;
+        (self.squared_deviations / (n - 1.0) / n).sqrt()
+    }
+}
+
+// The index is truncated to pick uniformly among the first i + 1 items.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn shuffle<T>(items: &mut [T], rng: &mut Rng) {
+    for i in (1..items.len()).rev() {
+        let j = (rng.next_float() * (i + 1) as Float) as usize;
+        items.swap(i, j.min(i));
+    }
+}
+
+/// Visualises sample counts as a grayscale image, where white is `max_samples`.
+pub fn sample_count_image(counts: &SampleCounts, max_samples: u32) -> Image {
+    let max = Float::from(max_samples.max(1));
+    counts
+        .iter()
+        .map(|col| {
+            col.iter()
+                .map(|&count| {
+                    let c = Float::from(count) / max;
+                    Color::new(c, c, c)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn blank_image(width: u32, height: u32) -> Image {
+    vec![vec![Color::new(0.0, 0.0, 0.0); height as usize]; width as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+
+    use na::*;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::trace::*;
+    use crate::view_transformation::*;
+
+    fn camera() -> Camera {
+        Camera {
+            canvas_width: 37,
+            canvas_height: 23,
+            field_of_view_radians: FRAC_PI_2,
+            transform: ViewTransformation {
+                from: Point3::new(0.0, 0.0, -3.0),
+                to: Point3::new(0.0, 0.0, 0.0),
+                up: Vector3::y(),
+            }
+            .matrix(),
+            ..Camera::default()
+        }
+    }
+
+    #[test]
+    fn it_covers_canvas_with_tiles() {
+        let renderer = Renderer {
+            threads: 1,
+            tile_size: 8,
+            ..Renderer::default()
+        };
+        let tiles = renderer.tiles(&camera());
+        assert_eq!(tiles.len(), 5 * 3);
+        let area: u32 = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 37 * 23);
+    }
+
+    #[test]
+    fn it_renders_same_image_with_many_threads() {
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let camera = camera();
+        let image = Renderer {
+            threads: 4,
+            tile_size: 5,
+            ..Renderer::default()
+        }
+        .render(&camera, &scene);
+        assert_eq!(image.len(), 37);
+        assert_eq!(image[0].len(), 23);
+        for x in 0..camera.canvas_width {
+            for y in 0..camera.canvas_height {
+                let center = (Float::from(x) + 0.5, Float::from(y) + 0.5);
+                assert_eq!(
+                    image[x as usize][y as usize],
+                    render_ray(
+                        &scene,
+                        &camera.ray_through(center.0, center.1),
+                        &mut Rng::new(0, 0)
+                    )
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_averages_samples() {
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let camera = camera();
+        let renderer = Renderer {
+            samples_per_pixel: 4,
+            ..Renderer::default()
+        };
+        let expected = [(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+            .iter()
+            .fold(Color::new(0.0, 0.0, 0.0), |total, (dx, dy)| {
+                total
+                    + render_ray(
+                        &scene,
+                        &camera.ray_through(18.0 + dx, 11.0 + dy),
+                        &mut Rng::new(0, 0),
+                    )
+            })
+            * 0.25;
+        assert_eq!(
+            renderer.render_pixel(&camera, &scene, 18, 11),
+            (expected, 4)
+        );
+    }
+
+    #[test]
+    fn it_reproduces_random_patterns() {
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let camera = camera();
+        for pattern in &[
+            SamplePattern::Jittered,
+            SamplePattern::Halton,
+            SamplePattern::Sobol,
+        ] {
+            let renderer = Renderer {
+                samples_per_pixel: 8,
+                pattern: *pattern,
+                filter: Filter::Mitchell,
+                ..Renderer::default()
+            };
+            assert_eq!(
+                renderer.render_pixel(&camera, &scene, 18, 11),
+                renderer.render_pixel(&camera, &scene, 18, 11)
+            );
+        }
+    }
+
+    #[test]
+    fn it_weights_samples_by_filter() {
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let camera = camera();
+        let renderer = Renderer {
+            samples_per_pixel: 4,
+            filter: Filter::Tent,
+            ..Renderer::default()
+        };
+        // The grid covers the tent's footprint, where every sample gets the same weight.
+        let expected = [(-0.5, -0.5), (0.5, -0.5), (-0.5, 0.5), (0.5, 0.5)]
+            .iter()
+            .fold(Color::new(0.0, 0.0, 0.0), |total, (dx, dy)| {
+                total
+                    + render_ray(
+                        &scene,
+                        &camera.ray_through(18.5 + dx, 11.5 + dy),
+                        &mut Rng::new(0, 0),
+                    ) * 0.25
+            });
+        let (Color(actual), _) = renderer.render_pixel(&camera, &scene, 18, 11);
+        assert!((actual - expected.0).norm() < 1e-12);
+    }
+
+    #[test]
+    fn it_estimates_standard_error() {
+        let mut variance = RunningVariance::default();
+        variance.add(1.0);
+        assert!(variance.standard_error().is_infinite());
+        for &value in &[2.0, 3.0, 4.0] {
+            variance.add(value);
+        }
+        assert_relative_eq!(variance.mean, 2.5);
+        // The sample variance is 5 / 3.
+        assert_relative_eq!(variance.standard_error(), (5.0 / 3.0 / 4.0 as Float).sqrt());
+    }
+
+    #[test]
+    fn it_refines_only_where_needed() {
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let camera = camera();
+        let renderer = Renderer {
+            samples_per_pixel: 64,
+            pattern: SamplePattern::Jittered,
+            adaptive: Some(AdaptiveSampling {
+                min_samples: 4,
+                threshold: 0.001,
+            }),
+            ..Renderer::default()
+        };
+        let (_, counts) = renderer.render_with_sample_counts(&camera, &scene);
+        // The background is flat, but the sphere's silhouette is not.
+        assert_eq!(counts[0][0], 4);
+        assert!(counts.iter().any(|col| col[11] == 64));
+        assert!(counts
+            .iter()
+            .flatten()
+            .all(|count| (4..=64).contains(count)));
+
+        let debug = sample_count_image(&counts, 64);
+        assert_relative_eq!(debug[0][0].0.x, 4.0 / 64.0);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::types::*;
+
+/// A small PCG32 random number generator. Each pixel seeds its own so that renders are
+/// reproducible no matter which thread draws which tile.
+///
+/// <https://www.pcg-random.org/>
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+impl Rng {
+    /// Generators with different streams produce independent sequences even when seeded
+    /// the same.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Rng {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(mix(seed));
+        rng.next_u32();
+        rng
+    }
+
+    /// A generator for the pixel at (x, y).
+    pub fn for_pixel(x: u32, y: u32, stream: u64) -> Self {
+        Self::new(u64::from(x) << 32 | u64::from(y), stream)
+    }
+
+    // PCG's output is deliberately the low bits of the shifted state.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+        let xor_shifted = (((old >> 18) ^ old) >> 27) as u32;
+        xor_shifted.rotate_right((old >> 59) as u32)
+    }
+
+    /// Uniformly distributed in [0, 1).
+    pub fn next_float(&mut self) -> Float {
+        Float::from(self.next_u32()) / 4294967296.0
+    }
+}
+
+/// `SplitMix64`'s finalizer, so that neighbouring seeds start far apart.
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_is_deterministic() {
+        let mut a = Rng::for_pixel(3, 4, 0);
+        let mut b = Rng::for_pixel(3, 4, 0);
+        for _ in 0..10 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+        assert_ne!(
+            Rng::for_pixel(3, 4, 0).next_u32(),
+            Rng::for_pixel(4, 3, 0).next_u32()
+        );
+        assert_ne!(
+            Rng::for_pixel(3, 4, 0).next_u32(),
+            Rng::for_pixel(3, 4, 1).next_u32()
+        );
+    }
+
+    #[test]
+    fn it_draws_uniform_floats() {
+        let mut rng = Rng::new(42, 0);
+        let samples: Vec<Float> = (0..10000).map(|_| rng.next_float()).collect();
+        assert!(samples.iter().all(|&u| (0.0..1.0).contains(&u)));
+        let mean = samples.iter().sum::<Float>() / 10000.0;
+        assert!((mean - 0.5).abs() < 0.01);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::rng::*;
+use crate::types::*;
+
+/// How sample positions are spread over the unit square.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplePattern {
+    /// Evenly spaced on a square grid. The count is rounded up to a square number.
+    Grid,
+    /// One random point in each cell of a square grid. The count is rounded up to a square
+    /// number.
+    Jittered,
+    /// The Halton sequence in bases 2 and 3, randomly shifted per pixel.
+    Halton,
+    /// The first two dimensions of the Sobol sequence, randomly scrambled per pixel.
+    Sobol,
+}
+
+impl SamplePattern {
+    /// Whether every prefix of the generated points is itself well spread.
+    pub fn is_progressive(self) -> bool {
+        match self {
+            SamplePattern::Grid | SamplePattern::Jittered => false,
+            SamplePattern::Halton | SamplePattern::Sobol => true,
+        }
+    }
+
+    /// How many points `generate` produces when asked for `count`.
+    pub fn sample_count(self, count: u32) -> u32 {
+        match self {
+            SamplePattern::Grid | SamplePattern::Jittered => grid_size(count).pow(2),
+            SamplePattern::Halton | SamplePattern::Sobol => count.max(1),
+        }
+    }
+
+    /// Produces at least `count` points in [0, 1)².
+    pub fn generate(self, count: u32, rng: &mut Rng) -> Vec<(Float, Float)> {
+        match self {
+            SamplePattern::Grid | SamplePattern::Jittered => {
+                let size = grid_size(count);
+                let step = 1.0 / Float::from(size);
+                let mut points = Vec::with_capacity((size * size) as usize);
+                for j in 0..size {
+                    for i in 0..size {
+                        let (u, v) = match self {
+                            SamplePattern::Grid => (0.5, 0.5),
+                            _ => (rng.next_float(), rng.next_float()),
+                        };
+                        points.push(((Float::from(i) + u) * step, (Float::from(j) + v) * step));
+                    }
+                }
+                points
+            }
+            SamplePattern::Halton => {
+                let shift = (rng.next_float(), rng.next_float());
+                (0..count.max(1))
+                    .map(|i| {
+                        (
+                            (radical_inverse(i, 2) + shift.0).fract(),
+                            (radical_inverse(i, 3) + shift.1).fract(),
+                        )
+                    })
+                    .collect()
+            }
+            SamplePattern::Sobol => {
+                let scramble = (rng.next_u32(), rng.next_u32());
+                (0..count.max(1)).map(|i| sobol(i, scramble)).collect()
+            }
+        }
+    }
+}
+
+/// The number of samples along each side of a grid holding `count` samples.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub fn grid_size(count: u32) -> u32 {
+    (Float::from(count.max(1)).sqrt().ceil() as u32).max(1)
+}
+
+/// Mirrors the digits of `index` in `base` about the radix point.
+fn radical_inverse(mut index: u32, base: u32) -> Float {
+    let inverse_base = 1.0 / Float::from(base);
+    let mut scale = inverse_base;
+    let mut result = 0.0;
+    while index > 0 {
+        result += Float::from(index % base) * scale;
+        index /= base;
+        scale *= inverse_base;
+    }
+    result
+}
+
+/// Point `index` of the two dimensional Sobol sequence, XOR scrambled. Scrambling keeps
+/// the stratification of every power-of-two prefix.
+fn sobol(index: u32, scramble: (u32, u32)) -> (Float, Float) {
+    // The first dimension is the van der Corput sequence. The second uses the direction
+    // numbers of the primitive polynomial x + 1.
+    let x = index.reverse_bits();
+    let mut y = 0;
+    let mut direction = 1 << 31;
+    let mut bits = index;
+    while bits > 0 {
+        if bits & 1 == 1 {
+            y ^= direction;
+        }
+        bits >>= 1;
+        direction ^= direction >> 1;
+    }
+    let to_float = |v: u32| Float::from(v) / 4294967296.0;
+    (to_float(x ^ scramble.0), to_float(y ^ scramble.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn it_rounds_grids_up_to_square_numbers() {
+        assert_eq!(grid_size(0), 1);
+        assert_eq!(grid_size(1), 1);
+        assert_eq!(grid_size(4), 2);
+        assert_eq!(grid_size(5), 3);
+        let mut rng = Rng::new(0, 0);
+        assert_eq!(SamplePattern::Jittered.generate(5, &mut rng).len(), 9);
+        assert_eq!(SamplePattern::Jittered.sample_count(5), 9);
+        assert_eq!(SamplePattern::Halton.generate(5, &mut rng).len(), 5);
+        assert_eq!(SamplePattern::Halton.sample_count(5), 5);
+    }
+
+    #[test]
+    fn it_places_grid_samples_at_cell_centers() {
+        let points = SamplePattern::Grid.generate(4, &mut Rng::new(0, 0));
+        assert_eq!(
+            points,
+            vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]
+        );
+    }
+
+    #[test]
+    fn it_jitters_within_cells() {
+        let points = SamplePattern::Jittered.generate(16, &mut Rng::new(1, 0));
+        for (k, (u, v)) in (0u32..).zip(&points) {
+            let (i, j) = (Float::from(k % 4), Float::from(k / 4));
+            assert!(*u >= i / 4.0 && *u < (i + 1.0) / 4.0);
+            assert!(*v >= j / 4.0 && *v < (j + 1.0) / 4.0);
+        }
+    }
+
+    #[test]
+    fn it_computes_radical_inverses() {
+        assert_relative_eq!(radical_inverse(0, 2), 0.0);
+        assert_relative_eq!(radical_inverse(1, 2), 0.5);
+        assert_relative_eq!(radical_inverse(6, 2), 0.375);
+        assert_relative_eq!(radical_inverse(5, 3), 7.0 / 9.0);
+    }
+
+    #[test]
+    fn it_generates_sobol_points() {
+        let points: Vec<_> = (0..4).map(|i| sobol(i, (0, 0))).collect();
+        assert_eq!(
+            points,
+            vec![(0.0, 0.0), (0.5, 0.5), (0.25, 0.75), (0.75, 0.25)]
+        );
+    }
+
+    #[test]
+    // Points in [0, 1) scale to cells 0 to 3.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn it_stratifies_scrambled_sobol_points() {
+        // Every 16 point prefix has exactly one point in each 4 by 4 cell.
+        let points = SamplePattern::Sobol.generate(16, &mut Rng::new(7, 0));
+        let mut cells: Vec<_> = points
+            .iter()
+            .map(|(u, v)| ((u * 4.0) as u32, (v * 4.0) as u32))
+            .collect();
+        cells.sort_unstable();
+        cells.dedup();
+        assert_eq!(cells.len(), 16);
+    }
+}
@@ -0,0 +1,211 @@
+//! Warps points in the unit square onto other domains, so that stratified or
+//! low-discrepancy points stay well spread after the mapping.
+
+use std::f64::consts::*;
+
+use crate::types::*;
+
+/// Shirley and Chiu's low distortion map from the square to the unit disk.
+pub fn concentric_disk(u: Float, v: Float) -> (Float, Float) {
+    let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, theta) = if x.abs() > y.abs() {
+        (x, FRAC_PI_4 * (y / x))
+    } else {
+        (y, FRAC_PI_2 - FRAC_PI_4 * (x / y))
+    };
+    (radius * theta.cos(), radius * theta.sin())
+}
+
+/// Two unit vectors perpendicular to the unit vector `n` and to each other.
+pub fn orthonormal_basis(n: &Vec3f) -> (Vec3f, Vec3f) {
+    let helper = if n.y.abs() < 0.9 {
+        Vec3f::y()
+    } else {
+        Vec3f::x()
+    };
+    let tangent = helper.cross(n).normalize();
+    (tangent, n.cross(&tangent))
+}
+
+/// A direction uniformly distributed over the unit sphere.
+pub fn uniform_sphere(u: Float, v: Float) -> Vec3f {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vec3f::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// A direction uniformly distributed over the cone of directions within an angle of
+/// `acos(cos_max)` of `axis`.
+pub fn uniform_cone(u: Float, v: Float, axis: &Vec3f, cos_max: Float) -> Vec3f {
+    let cos_theta = 1.0 - u * (1.0 - cos_max);
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta
+}
+
+/// A direction in the hemisphere around the unit vector `normal`, distributed in
+/// proportion to the cosine of its angle with `normal`, so its density is `cos θ / π`.
+pub fn cosine_hemisphere(u: Float, v: Float, normal: &Vec3f) -> Vec3f {
+    // Projecting points spread evenly over the disk up onto the hemisphere gives the
+    // cosine distribution (Malley's method).
+    let (x, y) = concentric_disk(u, v);
+    let height = (1.0 - x * x - y * y).max(0.0).sqrt();
+    let (tangent, bitangent) = orthonormal_basis(normal);
+    tangent * x + bitangent * y + normal * height
+}
+
+/// A direction around the unit vector `axis` with a density of
+/// `(exponent + 1) / 2π · cos^exponent θ`, where θ is its angle with `axis`. It may fall
+/// more than 90 degrees from `axis` only if `exponent` is below 0.
+pub fn cosine_power_lobe(u: Float, v: Float, axis: &Vec3f, exponent: Float) -> Vec3f {
+    let cos_theta = u.powf(1.0 / (exponent + 1.0));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    let (tangent, bitangent) = orthonormal_basis(axis);
+    tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + axis * cos_theta
+}
+
+/// Picks indices with probability proportional to their weights, by searching a
+/// cumulative distribution.
+pub struct Distribution {
+    /// The running totals of the weights, starting at 0.
+    cdf: Vec<Float>,
+}
+
+impl Distribution {
+    /// Negative weights count as zero.
+    pub fn new(weights: &[Float]) -> Self {
+        let mut cdf = Vec::with_capacity(weights.len() + 1);
+        cdf.push(0.0);
+        let mut total = 0.0;
+        for weight in weights {
+            total += weight.max(0.0);
+            cdf.push(total);
+        }
+        Self { cdf }
+    }
+
+    pub fn total(&self) -> Float {
+        self.cdf[self.cdf.len() - 1]
+    }
+
+    pub fn probability(&self, index: usize) -> Float {
+        (self.cdf[index + 1] - self.cdf[index]) / self.total()
+    }
+
+    /// Maps `u` in [0, 1) to an index, along with `u` stretched back over [0, 1) within
+    /// that index's share so it can be reused. The total weight must be positive.
+    pub fn sample(&self, u: Float) -> (usize, Float) {
+        let target = u * self.total();
+        // The first index whose running total passes the target, which skips over
+        // weightless entries.
+        let index = self.cdf[1..]
+            .partition_point(|&c| c <= target)
+            .min(self.cdf.len() - 2);
+        let (low, high) = (self.cdf[index], self.cdf[index + 1]);
+        let remapped = ((target - low) / (high - low)).min(1.0 - EPSILON);
+        (index, remapped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+
+    use super::*;
+
+    #[test]
+    fn it_maps_square_to_disk() {
+        assert_eq!(concentric_disk(0.5, 0.5), (0.0, 0.0));
+        let (x, y) = concentric_disk(1.0, 0.5);
+        assert_relative_eq!(x, 1.0);
+        assert_relative_eq!(y, 0.0);
+        let (x, y) = concentric_disk(0.5, 0.0);
+        assert_relative_eq!(x, 0.0, epsilon = 1e-12);
+        assert_relative_eq!(y, -1.0);
+    }
+
+    #[test]
+    fn it_builds_orthonormal_bases() {
+        for n in &[
+            Vec3f::x(),
+            Vec3f::y(),
+            -Vec3f::y(),
+            Vec3f::new(1.0, 2.0, -3.0).normalize(),
+        ] {
+            let (t, b) = orthonormal_basis(n);
+            assert_relative_eq!(t.norm(), 1.0, epsilon = 1e-12);
+            assert_relative_eq!(b.norm(), 1.0, epsilon = 1e-12);
+            assert_relative_eq!(t.dot(n), 0.0, epsilon = 1e-12);
+            assert_relative_eq!(b.dot(n), 0.0, epsilon = 1e-12);
+            assert_relative_eq!(t.dot(&b), 0.0, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn it_samples_directions_within_cones() {
+        let axis = Vec3f::new(0.0, 1.0, 1.0).normalize();
+        let cos_max = 0.8;
+        for i in 0..10 {
+            for j in 0..10 {
+                let (u, v) = (Float::from(i) / 10.0, Float::from(j) / 10.0);
+                let d = uniform_cone(u, v, &axis, cos_max);
+                assert_relative_eq!(d.norm(), 1.0, epsilon = 1e-12);
+                assert!(d.dot(&axis) >= cos_max - 1e-12);
+                assert_relative_eq!(uniform_sphere(u, v).norm(), 1.0, epsilon = 1e-12);
+            }
+        }
+        assert_relative_eq!(uniform_cone(0.0, 0.3, &axis, cos_max), axis);
+    }
+
+    #[test]
+    fn it_samples_cosine_weighted_hemispheres() {
+        let normal = Vec3f::new(1.0, -1.0, 0.5).normalize();
+        let mut mean_cosine = 0.0;
+        for i in 0..32 {
+            for j in 0..32 {
+                let (u, v) = ((Float::from(i) + 0.5) / 32.0, (Float::from(j) + 0.5) / 32.0);
+                let d = cosine_hemisphere(u, v, &normal);
+                assert_relative_eq!(d.norm(), 1.0, epsilon = 1e-12);
+                assert!(d.dot(&normal) >= 0.0);
+                mean_cosine += d.dot(&normal) / 1024.0;
+            }
+        }
+        // The mean of cos θ under the density cos θ / π is 2/3.
+        assert_relative_eq!(mean_cosine, 2.0 / 3.0, epsilon = 5e-3);
+    }
+
+    #[test]
+    fn it_samples_cosine_power_lobes() {
+        let axis = Vec3f::new(0.0, 1.0, 1.0).normalize();
+        let mut mean_cosine = 0.0;
+        for i in 0..32 {
+            for j in 0..32 {
+                let (u, v) = ((Float::from(i) + 0.5) / 32.0, (Float::from(j) + 0.5) / 32.0);
+                let d = cosine_power_lobe(u, v, &axis, 10.0);
+                assert_relative_eq!(d.norm(), 1.0, epsilon = 1e-12);
+                mean_cosine += d.dot(&axis) / 1024.0;
+            }
+        }
+        // The mean of cos θ under a density proportional to cos^n θ is (n + 1) / (n + 2).
+        assert_relative_eq!(mean_cosine, 11.0 / 12.0, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn it_samples_in_proportion_to_weights() {
+        let distribution = Distribution::new(&[1.0, 0.0, 3.0]);
+        assert_relative_eq!(distribution.total(), 4.0);
+        assert_relative_eq!(distribution.probability(0), 0.25);
+        assert_relative_eq!(distribution.probability(1), 0.0);
+        assert_eq!(distribution.sample(0.0), (0, 0.0));
+        assert_eq!(distribution.sample(0.125), (0, 0.5));
+        assert_eq!(distribution.sample(0.25), (2, 0.0));
+        assert_eq!(distribution.sample(0.625), (2, 0.5));
+        assert_eq!(distribution.sample(0.999_999).0, 2);
+    }
+}
@@ -1,24 +1,37 @@
 use na::Point3;
 
+use crate::bvh::*;
 use crate::color::*;
+use crate::emitters::*;
+use crate::environment_light::*;
+use crate::light::*;
 use crate::material::*;
 use crate::model_transformation::*;
 use crate::pattern::*;
 use crate::point_light::*;
 use crate::ray::*;
+use crate::rng::*;
 use crate::shape::*;
 use crate::sphere::*;
 use crate::types::*;
 
+/// How many times a ray may bounce off reflective surfaces before we give up on it.
+pub const DEFAULT_MAX_DEPTH: u32 = 5;
+
 pub struct Scene {
     pub objects: Vec<Box<Shape>>,
-    pub lights: Vec<PointLight>,
+    pub lights: Vec<Box<Light>>,
+    /// Lights the scene from all around and is seen by rays that hit nothing.
+    pub environment: Option<EnvironmentLight>,
+    pub max_depth: u32,
+    bvh: Bvh,
+    emitters: Emitters,
 }
 
 impl Default for Scene {
     fn default() -> Self {
         let obj = Box::new(Sphere {
-            object_to_world_space: ModelTransformation::new().matrix(),
+            object_to_world_space: ModelTransformation::new().matrix().into(),
             material: Material {
                 color: Box::new(SolidPattern(Color::new(1.0, 0.2, 1.0))),
                 ..Material::default()
@@ -27,42 +40,61 @@ impl Default for Scene {
         let light = PointLight {
             color: Color::new(1.0, 1.0, 1.0),
             position: Point3::new(-10.0, 10.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
         };
         Self {
             objects: vec![obj],
-            lights: vec![light],
+            lights: vec![Box::new(light)],
+            ..Self::new()
         }
     }
 }
 
 impl Scene {
-    pub fn intersection(&self, ray: &Ray) -> Option<(Float, &Shape)> {
-        let mut min_intersection = None;
-        for obj in &self.objects {
-            match (min_intersection, obj.intersection(ray)) {
-                (None, Some(t)) => min_intersection = Some((t, &**obj)),
-                (Some((min_t, _)), Some(t)) => {
-                    if t < min_t {
-                        min_intersection = Some((t, &**obj));
-                    }
-                }
-                _ => {}
-            }
+    pub fn new() -> Self {
+        Self {
+            objects: vec![],
+            lights: vec![],
+            environment: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            bvh: Bvh::default(),
+            emitters: Emitters::new(&[]),
         }
+    }
 
-        min_intersection
+    /// Builds the acceleration structure over the current objects, and finds the ones
+    /// that glow. This should be called once the scene is populated and again if objects
+    /// are changed or removed; until then queries fall back to testing every object and
+    /// glowing objects are only seen, not sampled.
+    pub fn build_bvh(&mut self) {
+        self.bvh = Bvh::build(&self.objects);
+        self.emitters = Emitters::new(&self.objects);
+    }
+
+    pub fn intersection(&self, ray: &Ray) -> Option<(Float, &Shape)> {
+        self.bvh.intersection(&self.objects, ray)
     }
 
     pub fn is_occluded(&self, ray: &Ray, distance_threshold: Float) -> bool {
-        for obj in &self.objects {
-            if let Some(t) = obj.intersection(ray) {
-                if t < distance_threshold {
-                    return true;
-                }
-            }
-        }
+        self.bvh.is_occluded(&self.objects, ray, distance_threshold)
+    }
+
+    /// Picks a point on one of the glowing objects to light `point` with.
+    pub fn sample_emitter(&self, point: &Point3f, rng: &mut Rng) -> Option<LightSample> {
+        self.emitters.sample(&self.objects, point, rng)
+    }
+
+    /// The density, per unit solid angle, with which `sample_emitter` picks the point
+    /// `hit` on `shape` to light `point` with.
+    pub fn emitter_pdf(&self, shape: &Shape, point: &Point3f, hit: &Point3f) -> Float {
+        self.emitters.pdf(shape, point, hit)
+    }
 
-        false
+    /// The lights followed by the environment, if there is one.
+    pub fn every_light(&self) -> impl Iterator<Item = &Light> {
+        let environment = self.environment.as_ref().map(|e| e as &Light);
+        self.lights.iter().map(AsRef::as_ref).chain(environment)
     }
 }
 
@@ -71,13 +103,14 @@ mod tests {
     use super::*;
 
     fn setup(p: Point3f) -> (Scene, Float, Ray) {
-        let scene: Scene = Default::default();
-        let (distance, dir) = scene.lights[0].direction_from(&p);
+        let mut scene = Scene::default();
+        scene.build_bvh();
+        let sample = &scene.lights[0].samples(&p, &mut Rng::new(0, 0))[0];
         let shadow_ray = Ray {
             origin: p,
-            direction: dir,
+            direction: sample.direction,
         };
-        (scene, distance, shadow_ray)
+        (scene, sample.distance, shadow_ray)
     }
 
     #[test]
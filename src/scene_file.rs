@@ -0,0 +1,1456 @@
+//! A small declarative scene description format.
+//!
+//! A scene file is a list of items, one per line (or separated by `;`). Every item is a
+//! key followed by any number of values and optionally a `{ ... }` block of nested items.
+//! `#` starts a comment which runs to the end of the line.
+//!
+//! ```text
+//! max_depth 5
+//!
+//! camera {
+//!     width 500
+//!     height 400
+//!     field_of_view 90
+//!     from 0 0 -2
+//!     to 0 0 0
+//!     up 0 1 0
+//! }
+//!
+//! light {
+//!     position -1 -1 -1.5
+//!     color 1 1 1
+//!     intensity 4
+//! }
+//!
+//! material glass {
+//!     transparency 0.9
+//!     refractive_index 1.5
+//! }
+//!
+//! sphere {
+//!     material glass { color 1 0.8 0.8 }
+//!     scale 0.5 1 0.5
+//!     rotate_z 45
+//! }
+//! ```
+//!
+//! Planes may also be placed with one of `floor`, `ceiling`, `left_wall`, `right_wall` or
+//! `back_wall` followed by the plane's position along its normal.
+//!
+//! A camera's `projection` is `perspective` (the default), `orthographic` followed by the
+//! width of the view in world units, `fisheye` or `equirectangular`.
+//!
+//! A camera with an `aperture_radius` has depth of field, focused at `focal_distance`
+//! (by default the distance between `from` and `to`), and its aperture is a polygon if
+//! it has at least three `aperture_blades`.
+//!
+//! A `spot_light` is a `light` aimed along a `direction`, shining fully within
+//! `inner_angle` of it and fading out toward `outer_angle` (30 and 45 by default). A
+//! `directional_light` has only the `direction` its parallel rays travel in, like the
+//! sun's.
+//!
+//! Every light but a `directional_light` may have an `intensity` in watts per steradian,
+//! or a `power` in watts spread over the directions it shines in, and an `attenuation`:
+//! `inverse_square` (the default), `none`, or `constant linear quadratic` coefficients
+//! per meter. A `directional_light`'s `intensity` is in watts per square meter. Each
+//! light's `color` tints its intensity. Scene units are meters unless `meters_per_unit`
+//! says otherwise, so a scene modelled in centimeters with `meters_per_unit 0.01` is
+//! lit just like the same scene in meters.
+//!
+//! An `environment` surrounds the scene with the equirectangular Radiance `.hdr` image
+//! in `file`, lighting it from every direction with `samples` shadow rays aimed at its
+//! brightest parts and showing up wherever rays miss everything. It may be scaled by an
+//! `intensity` and turned with `rotate_y`.
+//!
+//! Besides these there are area lights, which cast soft shadows by sending
+//! `samples` shadow rays toward random points spread evenly over them: a
+//! `rectangle_light` has a `corner` and two edges, `edge_u` and `edge_v`, and a
+//! `sphere_light` has a `center` and a `radius`.
+//!
+//! A material with any of `roughness`, `metallic` or `ior` is physically based: its
+//! `color` is the base color of a GGX microfacet surface, which is rough by the given
+//! amount from 0 to 1 (0.5 by default), a metal if `metallic` is 1 (0 by default) and
+//! otherwise reflects as a dielectric with the index of refraction `ior` (1.5 by
+//! default). Its `diffuse`, `specular` and `shininess` are then ignored.
+//!
+//! Any shape glows if its material has an `emission` color, scaled by an
+//! `emission_strength` in watts per steradian per square meter. The path tracer lights
+//! the scene with glowing shapes by aiming shadow rays at them, except for planes and
+//! cylinders or cones that go on forever, which have no area to aim at. Those, and every
+//! glowing shape under Whitted ray tracing, only show up where rays hit them.
+//!
+//! Angles are in degrees. Shapes are positioned with the `translate`, `scale`,
+//! `rotate_x`, `rotate_y` and `rotate_z` steps of a `ModelTransformation`, and patterns are
+//! positioned within their object the same way. A shape's `material` may be an inline
+//! block, the name of a material defined anywhere in the file, or a name followed by a
+//! block of overrides.
+
+use std::collections::HashMap;
+use std::f64::consts::*;
+use std::fmt;
+use std::fs;
+use std::iter::Peekable;
+use std::path::Path;
+use std::vec::IntoIter;
+
+use na::*;
+
+use crate::area_light::*;
+use crate::camera::*;
+use crate::color::*;
+use crate::cone::*;
+use crate::cube::*;
+use crate::cylinder::*;
+use crate::directional_light::*;
+use crate::environment_light::*;
+use crate::hdr::*;
+use crate::light::*;
+use crate::material::*;
+use crate::model_transformation::*;
+use crate::obj_file::*;
+use crate::object_transform::*;
+use crate::pattern::*;
+use crate::plane::*;
+use crate::point_light::*;
+use crate::scene::*;
+use crate::shape::*;
+use crate::sphere::*;
+use crate::spot_light::*;
+use crate::triangle::*;
+use crate::types::*;
+use crate::view_transformation::*;
+
+pub struct SceneDescription {
+    pub camera: Camera,
+    pub scene: Scene,
+    /// Problems that did not prevent loading, such as unsupported statements in meshes.
+    pub warnings: Vec<String>,
+}
+
+/// A problem with the scene file at a particular line and column, both counted from 1.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    /// The key of the item the problem was found in.
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: `{}`: {}",
+            self.line, self.column, self.key, self.message
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    Io(std::io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::Io(e) => write!(f, "{e}"),
+            SceneFileError::Parse(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneFileError {
+    fn from(e: std::io::Error) -> Self {
+        SceneFileError::Io(e)
+    }
+}
+
+impl From<ParseError> for SceneFileError {
+    fn from(e: ParseError) -> Self {
+        SceneFileError::Parse(e)
+    }
+}
+
+pub fn load_scene(path: &Path) -> Result<SceneDescription, SceneFileError> {
+    let source = fs::read_to_string(path)?;
+    let base_directory = path.parent().unwrap_or_else(|| Path::new("."));
+    Ok(parse_scene(&source, base_directory)?)
+}
+
+/// Parses a scene from source. Files referenced by the scene, such as meshes, are found
+/// relative to `base_directory`.
+pub fn parse_scene(source: &str, base_directory: &Path) -> Result<SceneDescription, ParseError> {
+    let items = parse_items(&mut tokenize(source)?.into_iter().peekable(), None)?;
+    Loader::new(base_directory, &items)?.load(&items)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Word(String),
+    Quoted(String),
+    Open,
+    Close,
+    Break,
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = vec![];
+    let mut chars = source.chars().peekable();
+    let (mut line, mut column) = (1, 1);
+    while let Some(&c) = chars.peek() {
+        let (start_line, start_column) = (line, column);
+        let kind = match c {
+            '#' => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                    column += 1;
+                }
+                continue;
+            }
+            '\n' | ';' => {
+                chars.next();
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+                TokenKind::Break
+            }
+            '{' | '}' => {
+                chars.next();
+                column += 1;
+                if c == '{' {
+                    TokenKind::Open
+                } else {
+                    TokenKind::Close
+                }
+            }
+            '"' => {
+                chars.next();
+                column += 1;
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(ParseError {
+                                line: start_line,
+                                column: start_column,
+                                key: text,
+                                message: "unterminated string".to_string(),
+                            });
+                        }
+                        Some(c) => text.push(c),
+                    }
+                    column += 1;
+                }
+                column += 1;
+                TokenKind::Quoted(text)
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                column += 1;
+                continue;
+            }
+            _ => {
+                let mut text = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};#\"".contains(c) {
+                        break;
+                    }
+                    text.push(c);
+                    chars.next();
+                    column += 1;
+                }
+                TokenKind::Word(text)
+            }
+        };
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug)]
+struct Value {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+struct Item {
+    key: Value,
+    values: Vec<Value>,
+    block: Option<Vec<Item>>,
+}
+
+/// Parses items until the end of the enclosing block, or of the file when `opened_by`
+/// is None.
+fn parse_items(
+    tokens: &mut Peekable<IntoIter<Token>>,
+    opened_by: Option<&Value>,
+) -> Result<Vec<Item>, ParseError> {
+    let mut items = vec![];
+    loop {
+        let Some(token) = tokens.next() else {
+            return match opened_by {
+                Some(key) => Err(error_at(key, &key.text, "unclosed `{`")),
+                None => Ok(items),
+            };
+        };
+        let key = Value {
+            text: match &token.kind {
+                TokenKind::Break => continue,
+                TokenKind::Close if opened_by.is_some() => return Ok(items),
+                TokenKind::Word(text) => text.clone(),
+                _ => {
+                    return Err(ParseError {
+                        line: token.line,
+                        column: token.column,
+                        key: opened_by.map_or(String::new(), |key| key.text.clone()),
+                        message: "expected a key".to_string(),
+                    });
+                }
+            },
+            line: token.line,
+            column: token.column,
+        };
+
+        let mut values = vec![];
+        while let Some(Token {
+            kind: TokenKind::Word(text) | TokenKind::Quoted(text),
+            line,
+            column,
+        }) = tokens.peek()
+        {
+            values.push(Value {
+                text: text.clone(),
+                line: *line,
+                column: *column,
+            });
+            tokens.next();
+        }
+
+        let block = match tokens.peek().map(|t| &t.kind) {
+            Some(TokenKind::Open) => {
+                tokens.next();
+                Some(parse_items(tokens, Some(&key))?)
+            }
+            _ => None,
+        };
+        items.push(Item { key, values, block });
+    }
+}
+
+fn error_at(value: &Value, key: &str, message: &str) -> ParseError {
+    ParseError {
+        line: value.line,
+        column: value.column,
+        key: key.to_string(),
+        message: message.to_string(),
+    }
+}
+
+impl Item {
+    fn error(&self, message: &str) -> ParseError {
+        error_at(&self.key, &self.key.text, message)
+    }
+
+    fn value_error(&self, value: &Value, message: &str) -> ParseError {
+        error_at(value, &self.key.text, message)
+    }
+
+    fn expect_value_count(&self, count: usize) -> Result<(), ParseError> {
+        if self.values.len() < count {
+            return Err(self.error(&format!(
+                "expected {} value{}",
+                count,
+                if count == 1 { "" } else { "s" }
+            )));
+        }
+        if let Some(extra) = self.values.get(count) {
+            return Err(self.value_error(extra, "unexpected value"));
+        }
+        Ok(())
+    }
+
+    fn expect_no_block(&self) -> Result<(), ParseError> {
+        match self.block {
+            Some(_) => Err(self.error("unexpected block")),
+            None => Ok(()),
+        }
+    }
+
+    fn block(&self) -> Result<&[Item], ParseError> {
+        self.block
+            .as_deref()
+            .ok_or_else(|| self.error("expected a `{ ... }` block"))
+    }
+
+    fn floats(&self, count: usize) -> Result<Vec<Float>, ParseError> {
+        self.expect_value_count(count)?;
+        self.expect_no_block()?;
+        self.values
+            .iter()
+            .map(|v| {
+                v.text
+                    .parse::<Float>()
+                    .map_err(|_| self.value_error(v, "expected a number"))
+            })
+            .collect()
+    }
+
+    fn float(&self) -> Result<Float, ParseError> {
+        Ok(self.floats(1)?[0])
+    }
+
+    fn angle(&self) -> Result<Float, ParseError> {
+        Ok(self.float()?.to_radians())
+    }
+
+    fn unsigned(&self) -> Result<u32, ParseError> {
+        self.expect_value_count(1)?;
+        self.expect_no_block()?;
+        self.values[0]
+            .text
+            .parse()
+            .map_err(|_| self.value_error(&self.values[0], "expected a whole number"))
+    }
+
+    fn boolean(&self) -> Result<bool, ParseError> {
+        self.expect_value_count(1)?;
+        self.expect_no_block()?;
+        match self.values[0].text.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(self.value_error(&self.values[0], "expected `true` or `false`")),
+        }
+    }
+
+    fn text(&self) -> Result<&str, ParseError> {
+        self.expect_value_count(1)?;
+        self.expect_no_block()?;
+        Ok(&self.values[0].text)
+    }
+
+    fn point(&self) -> Result<Point3f, ParseError> {
+        let v = self.floats(3)?;
+        Ok(Point3::new(v[0], v[1], v[2]))
+    }
+
+    fn vector(&self) -> Result<Vec3f, ParseError> {
+        let v = self.floats(3)?;
+        Ok(Vector3::new(v[0], v[1], v[2]))
+    }
+
+    fn color(&self) -> Result<Color, ParseError> {
+        let v = self.floats(3)?;
+        Ok(Color::new(v[0], v[1], v[2]))
+    }
+}
+
+fn projection(item: &Item) -> Result<Projection, ParseError> {
+    item.expect_no_block()?;
+    let kind = item
+        .values
+        .first()
+        .ok_or_else(|| item.error("expected a projection"))?;
+    if kind.text == "orthographic" {
+        item.expect_value_count(2)?;
+        let width = item.values[1]
+            .text
+            .parse()
+            .map_err(|_| item.value_error(&item.values[1], "expected a width"))?;
+        return Ok(Projection::Orthographic { width });
+    }
+
+    item.expect_value_count(1)?;
+    match kind.text.as_str() {
+        "perspective" => Ok(Projection::Perspective),
+        "fisheye" => Ok(Projection::Fisheye),
+        "equirectangular" => Ok(Projection::Equirectangular),
+        _ => Err(item.value_error(kind, "unknown projection")),
+    }
+}
+
+fn attenuation(item: &Item) -> Result<Attenuation, ParseError> {
+    item.expect_no_block()?;
+    if item.values.len() == 3 {
+        let v = item.floats(3)?;
+        return Ok(Attenuation {
+            constant: v[0],
+            linear: v[1],
+            quadratic: v[2],
+        });
+    }
+
+    item.expect_value_count(1)?;
+    match item.values[0].text.as_str() {
+        "none" => Ok(Attenuation::NONE),
+        "inverse_square" => Ok(Attenuation::INVERSE_SQUARE),
+        _ => Err(item.value_error(&item.values[0], "unknown attenuation")),
+    }
+}
+
+/// The material's microfacet parameters, which any of them turns on.
+fn microfacet(material: &mut Material) -> &mut Microfacet {
+    material.microfacet.get_or_insert_with(Microfacet::default)
+}
+
+fn unknown_key(item: &Item) -> ParseError {
+    item.error("unknown key")
+}
+
+/// Applies `item` to `t` if it is one of the transformation steps, returning whether it
+/// was.
+fn apply_transformation(t: &mut ModelTransformation, item: &Item) -> Result<bool, ParseError> {
+    *t = match item.key.text.as_str() {
+        "translate" => {
+            let v = item.floats(3)?;
+            t.translate(v[0], v[1], v[2])
+        }
+        "scale" => {
+            let v = item.floats(3)?;
+            t.scale(v[0], v[1], v[2])
+        }
+        "rotate_x" => t.rotate_x(item.angle()?),
+        "rotate_y" => t.rotate_y(item.angle()?),
+        "rotate_z" => t.rotate_z(item.angle()?),
+        _ => return Ok(false),
+    };
+    Ok(true)
+}
+
+/// The keys shared by every light that has a position.
+struct LightSettings {
+    color: Color,
+    intensity: Float,
+    /// Overrides `intensity` once the light's shape is known.
+    power: Option<Float>,
+    /// With coefficients per meter.
+    attenuation: Attenuation,
+}
+
+impl Default for LightSettings {
+    fn default() -> Self {
+        Self {
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            power: None,
+            attenuation: Attenuation::INVERSE_SQUARE,
+        }
+    }
+}
+
+impl LightSettings {
+    /// Applies `item` if it is one of the shared keys, returning whether it was.
+    fn apply(&mut self, item: &Item) -> Result<bool, ParseError> {
+        match item.key.text.as_str() {
+            "color" => self.color = item.color()?,
+            "intensity" => {
+                self.intensity = item.float()?;
+                self.power = None;
+            }
+            "power" => self.power = Some(item.float()?),
+            "attenuation" => self.attenuation = attenuation(item)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The intensity in watts per steradian, for a light that shines into `solid_angle`
+    /// steradians.
+    fn intensity(&self, solid_angle: Float) -> Float {
+        self.power
+            .map_or(self.intensity, |power| power / solid_angle)
+    }
+}
+
+/// Builds a plane at a position, such as `Plane::floor`.
+type PlanePreset = fn(Float, Material) -> Plane;
+
+struct Loader<'a> {
+    base_directory: &'a Path,
+    materials: HashMap<&'a str, &'a Item>,
+    meters_per_unit: Float,
+}
+
+impl<'a> Loader<'a> {
+    /// Collects the named material definitions and the scale of the scene up front so
+    /// they may be used before they are defined.
+    fn new(base_directory: &'a Path, items: &'a [Item]) -> Result<Self, ParseError> {
+        let mut meters_per_unit = 1.0;
+        for item in items
+            .iter()
+            .filter(|item| item.key.text == "meters_per_unit")
+        {
+            meters_per_unit = item.float()?;
+            if meters_per_unit <= 0.0 {
+                return Err(item.value_error(&item.values[0], "expected a positive length"));
+            }
+        }
+
+        let mut materials = HashMap::new();
+        for item in items.iter().filter(|item| item.key.text == "material") {
+            item.expect_value_count(1)?;
+            item.block()?;
+            let name = item.values[0].text.as_str();
+            if materials.insert(name, item).is_some() {
+                return Err(item.value_error(&item.values[0], "material is already defined"));
+            }
+        }
+        Ok(Self {
+            base_directory,
+            materials,
+            meters_per_unit,
+        })
+    }
+
+    fn load(&self, items: &[Item]) -> Result<SceneDescription, ParseError> {
+        let mut scene = Scene::new();
+        let mut camera = None;
+        let mut warnings = vec![];
+        for item in items {
+            match item.key.text.as_str() {
+                "camera" => {
+                    if camera.is_some() {
+                        return Err(item.error("camera is already defined"));
+                    }
+                    camera = Some(Self::camera(item)?);
+                }
+                "directional_light" => scene.lights.push(Box::new(Self::directional_light(item)?)),
+                "environment" => {
+                    if scene.environment.is_some() {
+                        return Err(item.error("environment is already defined"));
+                    }
+                    scene.environment = Some(self.environment(item)?);
+                }
+                "light" => scene.lights.push(Box::new(self.light(item)?)),
+                // Already read up front.
+                "material" | "meters_per_unit" => {}
+                "max_depth" => scene.max_depth = item.unsigned()?,
+                "mesh" => scene.objects.extend(self.mesh(item, &mut warnings)?),
+                "rectangle_light" => scene.lights.push(Box::new(self.rectangle_light(item)?)),
+                "sphere_light" => scene.lights.push(Box::new(self.sphere_light(item)?)),
+                "spot_light" => scene.lights.push(Box::new(self.spot_light(item)?)),
+                "cone" | "cube" | "cylinder" | "plane" | "sphere" | "triangle" => {
+                    scene.objects.push(self.shape(item)?);
+                }
+                _ => return Err(unknown_key(item)),
+            }
+        }
+        scene.build_bvh();
+
+        Ok(SceneDescription {
+            camera: camera.unwrap_or_default(),
+            scene,
+            warnings,
+        })
+    }
+
+    fn camera(item: &Item) -> Result<Camera, ParseError> {
+        let mut camera = Camera::default();
+        let mut view = ViewTransformation::default();
+        let mut focal_distance = None;
+        for child in item.block()? {
+            match child.key.text.as_str() {
+                "width" => camera.canvas_width = child.unsigned()?,
+                "height" => camera.canvas_height = child.unsigned()?,
+                "field_of_view" => camera.field_of_view_radians = child.angle()?,
+                "from" => view.from = child.point()?,
+                "to" => view.to = child.point()?,
+                "up" => view.up = child.vector()?,
+                "projection" => camera.projection = projection(child)?,
+                "aperture_radius" => camera.aperture_radius = child.float()?,
+                "focal_distance" => focal_distance = Some(child.float()?),
+                "aperture_blades" => camera.aperture_blades = child.unsigned()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        camera.transform = view.matrix();
+        // Unless told otherwise, focus on the point the camera looks at.
+        camera.focal_distance = focal_distance.unwrap_or_else(|| (view.to - view.from).norm());
+        Ok(camera)
+    }
+
+    fn light(&self, item: &Item) -> Result<PointLight, ParseError> {
+        let mut light = PointLight::default();
+        let mut settings = LightSettings::default();
+        for child in item.block()? {
+            if settings.apply(child)? {
+                continue;
+            }
+            match child.key.text.as_str() {
+                "position" => light.position = child.point()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        light.color = settings.color;
+        light.intensity = settings.intensity(4.0 * PI);
+        light.attenuation = settings.attenuation.in_units(self.meters_per_unit);
+        Ok(light)
+    }
+
+    fn spot_light(&self, item: &Item) -> Result<SpotLight, ParseError> {
+        let mut light = SpotLight {
+            color: Color::new(1.0, 1.0, 1.0),
+            position: Point3::new(0.0, 0.0, 0.0),
+            direction: -Vector3::y(),
+            inner_angle: DEFAULT_INNER_ANGLE,
+            outer_angle: DEFAULT_OUTER_ANGLE,
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+        };
+        let mut settings = LightSettings::default();
+        for child in item.block()? {
+            if settings.apply(child)? {
+                continue;
+            }
+            match child.key.text.as_str() {
+                "position" => light.position = child.point()?,
+                "direction" => light.direction = child.vector()?,
+                "inner_angle" => light.inner_angle = child.angle()?,
+                "outer_angle" => light.outer_angle = child.angle()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        light.color = settings.color;
+        light.intensity = settings.intensity(light.solid_angle());
+        light.attenuation = settings.attenuation.in_units(self.meters_per_unit);
+        Ok(light)
+    }
+
+    fn directional_light(item: &Item) -> Result<DirectionalLight, ParseError> {
+        let mut light = DirectionalLight {
+            color: Color::new(1.0, 1.0, 1.0),
+            direction: -Vector3::y(),
+            intensity: 1.0,
+        };
+        for child in item.block()? {
+            match child.key.text.as_str() {
+                "direction" => light.direction = child.vector()?,
+                "color" => light.color = child.color()?,
+                "intensity" => light.intensity = child.float()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        Ok(light)
+    }
+
+    fn environment(&self, item: &Item) -> Result<EnvironmentLight, ParseError> {
+        let mut file = None;
+        let mut intensity = 1.0;
+        let mut rotation = 0.0;
+        let mut samples = DEFAULT_LIGHT_SAMPLES;
+        for child in item.block()? {
+            match child.key.text.as_str() {
+                "file" => file = Some((child, child.text()?)),
+                "intensity" => intensity = child.float()?,
+                "rotate_y" => rotation = child.angle()?,
+                "samples" => samples = child.unsigned()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+
+        let (file_item, path) = file.ok_or_else(|| item.error("expected `file`"))?;
+        let image = read_hdr(&self.base_directory.join(path))
+            .map_err(|e| file_item.value_error(&file_item.values[0], &e.to_string()))?;
+        Ok(EnvironmentLight::new(image, intensity, rotation, samples))
+    }
+
+    fn rectangle_light(&self, item: &Item) -> Result<RectangleLight, ParseError> {
+        let mut light = RectangleLight {
+            corner: Point3::new(0.0, 0.0, 0.0),
+            edge_u: Vector3::x(),
+            edge_v: Vector3::z(),
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+            samples: DEFAULT_LIGHT_SAMPLES,
+        };
+        let mut settings = LightSettings::default();
+        for child in item.block()? {
+            if settings.apply(child)? {
+                continue;
+            }
+            match child.key.text.as_str() {
+                "corner" => light.corner = child.point()?,
+                "edge_u" => light.edge_u = child.vector()?,
+                "edge_v" => light.edge_v = child.vector()?,
+                "samples" => light.samples = child.unsigned()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        light.color = settings.color;
+        light.intensity = settings.intensity(4.0 * PI);
+        light.attenuation = settings.attenuation.in_units(self.meters_per_unit);
+        Ok(light)
+    }
+
+    fn sphere_light(&self, item: &Item) -> Result<SphereLight, ParseError> {
+        let mut light = SphereLight {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            color: Color::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+            samples: DEFAULT_LIGHT_SAMPLES,
+        };
+        let mut settings = LightSettings::default();
+        for child in item.block()? {
+            if settings.apply(child)? {
+                continue;
+            }
+            match child.key.text.as_str() {
+                "center" => light.center = child.point()?,
+                "radius" => light.radius = child.float()?,
+                "samples" => light.samples = child.unsigned()?,
+                _ => return Err(unknown_key(child)),
+            }
+        }
+        light.color = settings.color;
+        light.intensity = settings.intensity(4.0 * PI);
+        light.attenuation = settings.attenuation.in_units(self.meters_per_unit);
+        Ok(light)
+    }
+
+    /// Builds a shape's material from a `material` item, which names a defined material,
+    /// has an inline block, or both.
+    fn material(&self, item: &Item) -> Result<Material, ParseError> {
+        let mut material = Material::default();
+        match item.values.as_slice() {
+            [] => {
+                Self::apply_material_items(&mut material, item.block()?)?;
+            }
+            [name] => {
+                material = self.named_material(item, name)?;
+                if let Some(overrides) = &item.block {
+                    Self::apply_material_items(&mut material, overrides)?;
+                }
+            }
+            [_, extra, ..] => return Err(item.value_error(extra, "unexpected value")),
+        }
+        Ok(material)
+    }
+
+    fn named_material(&self, item: &Item, name: &Value) -> Result<Material, ParseError> {
+        let definition = self
+            .materials
+            .get(name.text.as_str())
+            .ok_or_else(|| item.value_error(name, "unknown material"))?;
+        let mut material = Material::default();
+        Self::apply_material_items(&mut material, definition.block()?)?;
+        Ok(material)
+    }
+
+    fn apply_material_items(material: &mut Material, items: &[Item]) -> Result<(), ParseError> {
+        for item in items {
+            match item.key.text.as_str() {
+                "color" => material.color = Box::new(SolidPattern(item.color()?)),
+                "pattern" => material.color = Self::pattern(item)?,
+                "ambient" => material.ambient = item.float()?,
+                "diffuse" => material.diffuse = item.float()?,
+                "specular" => material.specular = item.float()?,
+                "shininess" => material.shininess = item.float()?,
+                "reflective" => material.reflective = item.float()?,
+                "transparency" => material.transparency = item.float()?,
+                "refractive_index" => material.refractive_index = item.float()?,
+                "emission" => material.emission = item.color()?,
+                "emission_strength" => material.emission_strength = item.float()?,
+                "roughness" => microfacet(material).roughness = item.float()?,
+                "metallic" => microfacet(material).metallic = item.float()?,
+                "ior" => microfacet(material).ior = item.float()?,
+                _ => return Err(unknown_key(item)),
+            }
+        }
+        Ok(())
+    }
+
+    fn pattern(item: &Item) -> Result<Box<Pattern>, ParseError> {
+        item.expect_value_count(1)?;
+        let kind = &item.values[0];
+        let mut a = Color::new(1.0, 1.0, 1.0);
+        let mut b = Color::new(0.0, 0.0, 0.0);
+        let mut t = ModelTransformation::new();
+        for child in item.block()? {
+            match child.key.text.as_str() {
+                "a" => a = child.color()?,
+                "b" => b = child.color()?,
+                _ => {
+                    if !apply_transformation(&mut t, child)? {
+                        return Err(unknown_key(child));
+                    }
+                }
+            }
+        }
+
+        // The transformation places the pattern within the object, so mapping object
+        // points into the pattern takes its inverse.
+        let object_to_pattern_space = t.matrix().inverse();
+        Ok(match kind.text.as_str() {
+            "solid" => Box::new(SolidPattern(a)),
+            "stripe" => Box::new(StripePattern {
+                a,
+                b,
+                object_to_pattern_space,
+            }),
+            "gradient" => Box::new(GradientPattern {
+                a,
+                b,
+                object_to_pattern_space,
+            }),
+            "ring" => Box::new(RingPattern {
+                a,
+                b,
+                object_to_pattern_space,
+            }),
+            "checkers" => Box::new(CheckersPattern {
+                a,
+                b,
+                object_to_pattern_space,
+            }),
+            _ => return Err(item.value_error(kind, "unknown pattern")),
+        })
+    }
+
+    fn shape(&self, item: &Item) -> Result<Box<Shape>, ParseError> {
+        let mut t = ModelTransformation::new();
+        let mut material = Material::default();
+        let mut extents = (-1.0, 1.0);
+        let mut closed = false;
+        let mut points = vec![];
+        let mut preset: Option<(PlanePreset, Float)> = None;
+        for child in item.block()? {
+            if apply_transformation(&mut t, child)? {
+                continue;
+            }
+            match (item.key.text.as_str(), child.key.text.as_str()) {
+                (_, "material") => material = self.material(child)?,
+                ("cylinder" | "cone", "minimum") => extents.0 = child.float()?,
+                ("cylinder" | "cone", "maximum") => extents.1 = child.float()?,
+                ("cylinder" | "cone", "closed") => closed = child.boolean()?,
+                ("plane", "floor") => preset = Some((Plane::floor, child.float()?)),
+                ("plane", "ceiling") => preset = Some((Plane::ceiling, child.float()?)),
+                ("plane", "left_wall") => preset = Some((Plane::left_wall, child.float()?)),
+                ("plane", "right_wall") => preset = Some((Plane::right_wall, child.float()?)),
+                ("plane", "back_wall") => preset = Some((Plane::back_wall, child.float()?)),
+                ("triangle", "points") => {
+                    let v = child.floats(9)?;
+                    points = v.chunks(3).map(|p| Point3::new(p[0], p[1], p[2])).collect();
+                }
+                _ => return Err(unknown_key(child)),
+            }
+        }
+
+        let object_to_world_space = t.matrix().into();
+        let (minimum, maximum) = extents;
+        Ok(match item.key.text.as_str() {
+            "sphere" => Box::new(Sphere {
+                object_to_world_space,
+                material,
+            }),
+            "plane" => match preset {
+                Some((make_plane, position)) => Box::new(make_plane(position, material)),
+                None => Box::new(Plane {
+                    object_to_world_space,
+                    material,
+                }),
+            },
+            "cube" => Box::new(Cube {
+                object_to_world_space,
+                material,
+            }),
+            "cylinder" => Box::new(Cylinder {
+                object_to_world_space,
+                material,
+                minimum,
+                maximum,
+                closed,
+            }),
+            "cone" => Box::new(Cone {
+                object_to_world_space,
+                material,
+                minimum,
+                maximum,
+                closed,
+            }),
+            "triangle" => {
+                if points.is_empty() {
+                    return Err(item.error("expected `points`"));
+                }
+                let m = t.matrix();
+                Box::new(Triangle::new(
+                    m * points[0],
+                    m * points[1],
+                    m * points[2],
+                    material,
+                ))
+            }
+            _ => return Err(unknown_key(item)),
+        })
+    }
+
+    fn mesh(&self, item: &Item, warnings: &mut Vec<String>) -> Result<Vec<Box<Shape>>, ParseError> {
+        let mut t = ModelTransformation::new();
+        let mut file = None;
+        let mut material = None;
+        let mut group_materials = HashMap::new();
+        for child in item.block()? {
+            if apply_transformation(&mut t, child)? {
+                continue;
+            }
+            match child.key.text.as_str() {
+                "file" => file = Some((child, child.text()?)),
+                "material" => material = Some(child),
+                "group" => {
+                    // group <name> <material>
+                    child.expect_value_count(2)?;
+                    child.expect_no_block()?;
+                    group_materials.insert(child.values[0].text.as_str(), child);
+                }
+                _ => return Err(unknown_key(child)),
+            }
+        }
+
+        let (file_item, path) = file.ok_or_else(|| item.error("expected `file`"))?;
+        let mut obj = read_obj(&self.base_directory.join(path))
+            .map_err(|e| file_item.value_error(&file_item.values[0], &e.to_string()))?;
+        if obj.ignored_lines > 0 {
+            warnings.push(format!(
+                "{}: ignored {} unsupported line{}",
+                path,
+                obj.ignored_lines,
+                if obj.ignored_lines == 1 { "" } else { "s" }
+            ));
+        }
+
+        let transform: ObjectTransform = t.matrix().into();
+        for v in &mut obj.vertices {
+            *v = transform.matrix() * *v;
+        }
+        for n in &mut obj.normals {
+            *n = transform.normal_to_world_space(n);
+        }
+
+        // Build every material once up front so that building them per triangle below
+        // cannot fail.
+        if let Some(child) = material {
+            self.material(child)?;
+        }
+        for child in group_materials.values() {
+            self.named_material(child, &child.values[1])?;
+        }
+        let material_for_group = |group: &str| {
+            match (group_materials.get(group), material) {
+                (Some(child), _) => self.named_material(child, &child.values[1]),
+                (None, Some(child)) => self.material(child),
+                (None, None) => Ok(Material::default()),
+            }
+            .unwrap_or_default()
+        };
+        Ok(obj.triangles(material_for_group))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::*;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::ray::*;
+    use crate::rng::*;
+
+    fn parse(source: &str) -> Result<SceneDescription, ParseError> {
+        parse_scene(source, Path::new("."))
+    }
+
+    fn parse_error(source: &str) -> ParseError {
+        match parse(source) {
+            Ok(_) => panic!("expected a parse error"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn it_parses_camera() {
+        let description = parse(
+            "camera {
+                width 200
+                height 100
+                field_of_view 90
+                from 0 0 -5
+                to 0 0 0
+                up 0 1 0
+            }",
+        )
+        .unwrap();
+        let camera = description.camera;
+        assert_eq!(camera.canvas_width, 200);
+        assert_eq!(camera.canvas_height, 100);
+        assert_relative_eq!(camera.field_of_view_radians, FRAC_PI_2);
+        let r = camera.ray_for_pixel(100, 50);
+        assert_relative_eq!(r.origin, Point3::new(0.0, 0.0, -5.0));
+        assert_relative_eq!(camera.aperture_radius, 0.0);
+        assert_relative_eq!(camera.focal_distance, 5.0);
+    }
+
+    #[test]
+    fn it_parses_camera_lens() {
+        let camera = parse(
+            "camera {
+                from 0 0 -5
+                aperture_radius 0.1
+                focal_distance 3.5
+                aperture_blades 6
+            }",
+        )
+        .unwrap()
+        .camera;
+        assert_relative_eq!(camera.aperture_radius, 0.1);
+        assert_relative_eq!(camera.focal_distance, 3.5);
+        assert_eq!(camera.aperture_blades, 6);
+    }
+
+    #[test]
+    fn it_parses_camera_projections() {
+        let projection = |source: &str| {
+            parse(&format!("camera {{ projection {source} }}")).map(|d| d.camera.projection)
+        };
+        assert_eq!(projection("perspective"), Ok(Projection::Perspective));
+        assert_eq!(
+            projection("orthographic 12.5"),
+            Ok(Projection::Orthographic { width: 12.5 })
+        );
+        assert_eq!(projection("fisheye"), Ok(Projection::Fisheye));
+        assert_eq!(
+            projection("equirectangular"),
+            Ok(Projection::Equirectangular)
+        );
+        assert!(projection("orthographic").is_err());
+        assert!(projection("fisheye 12").is_err());
+        assert!(projection("cylindrical").is_err());
+    }
+
+    #[test]
+    fn it_parses_lights_and_shapes() {
+        let description = parse(
+            "# A lit sphere on a floor.
+            max_depth 3
+            light { position -10 10 -10; color 1 0.5 0.5 }
+            plane {}
+            sphere {
+                translate 0 1 0
+                material { color 1 0 0; reflective 0.5 }
+            }",
+        )
+        .unwrap();
+        let scene = description.scene;
+        assert_eq!(scene.max_depth, 3);
+        assert_eq!(scene.lights.len(), 1);
+        let sample = &scene.lights[0].samples(&Point3::origin(), &mut Rng::new(0, 0))[0];
+        // Dimmed by the square of its distance, √300.
+        assert_relative_eq!(sample.color.0, Vector3::new(1.0, 0.5, 0.5) / 300.0);
+        assert_eq!(scene.objects.len(), 2);
+        assert_relative_eq!(scene.objects[1].material().reflective, 0.5);
+
+        let r = Ray {
+            origin: Point3::new(0.0, 5.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        assert_relative_eq!(scene.intersection(&r).unwrap().0, 3.0);
+    }
+
+    #[test]
+    fn it_parses_area_lights() {
+        let scene = parse(
+            "rectangle_light {
+                corner -1 4 -1; edge_u 2 0 0; edge_v 0 0 2; samples 4; attenuation none
+            }
+            sphere_light { center 0 0 5; radius 0.5; color 1 0 0 }",
+        )
+        .unwrap()
+        .scene;
+        assert_eq!(scene.lights.len(), 2);
+        let mut rng = Rng::new(0, 0);
+        let rectangle = scene.lights[0].samples(&Point3::origin(), &mut rng);
+        assert_eq!(rectangle.len(), 4);
+        assert_relative_eq!(rectangle[0].color.0, Vector3::new(0.25, 0.25, 0.25));
+        let sphere = scene.lights[1].samples(&Point3::origin(), &mut rng);
+        assert_eq!(sphere.len(), DEFAULT_LIGHT_SAMPLES as usize);
+        assert!(sphere.iter().all(|s| s.distance >= 4.5 && s.distance < 5.0));
+        assert!(parse("sphere_light { position 0 0 0 }").is_err());
+    }
+
+    #[test]
+    fn it_parses_spot_and_directional_lights() {
+        let scene = parse(
+            "spot_light { position 0 10 0; direction 0 -1 0; inner_angle 10; outer_angle 20 }
+            directional_light { direction 1 -1 0; color 0.5 0.5 0.5 }",
+        )
+        .unwrap()
+        .scene;
+        assert_eq!(scene.lights.len(), 2);
+        let mut rng = Rng::new(0, 0);
+        assert_eq!(
+            scene.lights[0].samples(&Point3::origin(), &mut rng).len(),
+            1
+        );
+        // 45 degrees off the spotlight's axis.
+        let outside = Point3::new(10.0, 0.0, 0.0);
+        assert!(scene.lights[0].samples(&outside, &mut rng).is_empty());
+        let sun = &scene.lights[1].samples(&Point3::origin(), &mut rng)[0];
+        assert_relative_eq!(sun.direction, Vector3::new(-1.0, 1.0, 0.0).normalize());
+        assert!(sun.distance.is_infinite());
+    }
+
+    #[test]
+    fn it_parses_light_intensity_and_units() {
+        let scene = parse(
+            "meters_per_unit 0.01
+            light { position 0 200 0; intensity 8; attenuation inverse_square }
+            light { power 4; attenuation 1 0 1; color 1 0 0 }",
+        )
+        .unwrap()
+        .scene;
+        let mut rng = Rng::new(0, 0);
+        // 200 centimeters away.
+        let sample = &scene.lights[0].samples(&Point3::origin(), &mut rng)[0];
+        assert_relative_eq!(sample.color.0, Vector3::new(2.0, 2.0, 2.0), epsilon = 1e-12);
+        let sample = &scene.lights[1].samples(&Point3::new(100.0, 0.0, 0.0), &mut rng)[0];
+        let k = 4.0 / (4.0 * PI) / 2.0;
+        assert_relative_eq!(sample.color.0, Vector3::new(k, 0.0, 0.0), epsilon = 1e-12);
+
+        assert!(parse("light { attenuation cubic }").is_err());
+        assert!(parse("directional_light { power 1 }").is_err());
+        assert!(parse("meters_per_unit 0").is_err());
+    }
+
+    #[test]
+    fn it_dims_lights_with_distance_by_default() {
+        let scene = parse(
+            "light { position 0 1 0; intensity 4 }
+            light { position 0 1 0; intensity 4; attenuation none }",
+        )
+        .unwrap()
+        .scene;
+        let mut rng = Rng::new(0, 0);
+        for &(distance, dimmed) in &[(1.0, 4.0), (2.0, 1.0), (4.0, 0.25)] {
+            let point = Point3::new(0.0, 1.0 - distance, 0.0);
+            let sample = &scene.lights[0].samples(&point, &mut rng)[0];
+            assert_relative_eq!(sample.color.0.x, dimmed);
+            let sample = &scene.lights[1].samples(&point, &mut rng)[0];
+            assert_relative_eq!(sample.color.0.x, 4.0);
+        }
+    }
+
+    #[test]
+    fn it_parses_environments() {
+        let directory = std::env::temp_dir();
+        let name = format!("environment-{}.hdr", std::process::id());
+        let image = vec![vec![Color::new(0.5, 0.5, 0.5); 2]; 4];
+        fs::write(directory.join(&name), encode_hdr(&image)).unwrap();
+        let source =
+            format!("environment {{ file \"{name}\"; intensity 4; rotate_y 90; samples 9 }}");
+        let scene = parse_scene(&source, &directory).unwrap().scene;
+        fs::remove_file(directory.join(&name)).unwrap();
+
+        let environment = scene.environment.unwrap();
+        assert_relative_eq!(
+            environment.radiance(&Vector3::y()).0,
+            Vector3::new(2.0, 2.0, 2.0)
+        );
+        assert_relative_eq!(environment.rotation, FRAC_PI_2);
+        assert_eq!(environment.samples, 9);
+
+        let e = parse_error("environment { file \"missing.hdr\" }");
+        assert_eq!((e.line, e.column), (1, 20));
+        assert!(parse("environment { intensity 2 }").is_err());
+    }
+
+    #[test]
+    fn it_reuses_and_overrides_named_materials() {
+        let description = parse(
+            "sphere { material glass }
+            cube { material glass { refractive_index 2 } }
+            material glass {
+                transparency 0.9
+                refractive_index 1.5
+            }",
+        )
+        .unwrap();
+        let objects = &description.scene.objects;
+        assert_relative_eq!(objects[0].material().refractive_index, 1.5);
+        assert_relative_eq!(objects[1].material().refractive_index, 2.0);
+        assert_relative_eq!(objects[1].material().transparency, 0.9);
+    }
+
+    #[test]
+    fn it_parses_glowing_materials() {
+        let scene = parse(
+            "sphere { translate 0 5 0; material { emission 1 0.5 0; emission_strength 4 } }
+            plane { floor 0 }",
+        )
+        .unwrap()
+        .scene;
+        let emitted = scene.objects[0].material().emitted();
+        assert_relative_eq!(emitted.0, Vector3::new(4.0, 2.0, 0.0));
+        assert_relative_eq!(scene.objects[1].material().emitted().0, Vector3::zeros());
+        let sample = scene.sample_emitter(&Point3::origin(), &mut Rng::new(0, 0));
+        assert!(sample.unwrap().direction.y > 0.0);
+    }
+
+    #[test]
+    fn it_parses_microfacet_materials() {
+        let scene = parse(
+            "sphere { material { color 1 0.8 0.3; roughness 0.2; metallic 1 } }
+            sphere { material { ior 1.33 } }
+            sphere { }",
+        )
+        .unwrap()
+        .scene;
+        assert_eq!(
+            scene.objects[0].material().microfacet,
+            Some(Microfacet {
+                roughness: 0.2,
+                metallic: 1.0,
+                ior: 1.5,
+            })
+        );
+        assert_eq!(
+            scene.objects[1].material().microfacet,
+            Some(Microfacet {
+                ior: 1.33,
+                ..Microfacet::default()
+            })
+        );
+        assert_eq!(scene.objects[2].material().microfacet, None);
+    }
+
+    #[test]
+    fn it_parses_patterns() {
+        let description = parse(
+            "plane {
+                material {
+                    pattern checkers {
+                        a 1 1 1
+                        b 0 0 0
+                        scale 0.5 0.5 0.5
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let plane = &description.scene.objects[0];
+        assert_relative_eq!(
+            plane.color_at(&Point3::new(0.25, 0.0, 0.25)).0,
+            Vector3::new(1.0, 1.0, 1.0)
+        );
+        assert_relative_eq!(
+            plane.color_at(&Point3::new(0.75, 0.0, 0.25)).0,
+            Vector3::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_parses_plane_presets() {
+        let description = parse("plane { right_wall 3 }").unwrap();
+        let plane = &description.scene.objects[0];
+        assert_relative_eq!(
+            plane.normal_at(Point3::new(3.0, 0.0, 0.0)),
+            Vector3::new(-1.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_parses_cylinder_extents() {
+        let description = parse(
+            "cylinder {
+                minimum 0
+                maximum 2
+                closed true
+            }",
+        )
+        .unwrap();
+        let r = Ray {
+            origin: Point3::new(0.0, 5.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        assert_relative_eq!(description.scene.intersection(&r).unwrap().0, 3.0);
+    }
+
+    #[test]
+    fn it_reports_unknown_keys_with_position() {
+        let e = parse_error("sphere {\n    translate 0 1 0\n    wobble 3\n}");
+        assert_eq!(
+            e,
+            ParseError {
+                line: 3,
+                column: 5,
+                key: "wobble".to_string(),
+                message: "unknown key".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn it_reports_unknown_top_level_keys() {
+        let e = parse_error("lihgt 1 2 3");
+        assert_eq!((e.line, e.column), (1, 1));
+        assert_eq!(e.key, "lihgt");
+        assert_eq!(e.message, "unknown key");
+    }
+
+    #[test]
+    fn it_reports_malformed_values_with_position() {
+        let e = parse_error("light {\n  position 1 two 3\n}");
+        assert_eq!((e.line, e.column), (2, 14));
+        assert_eq!(e.key, "position");
+        assert_eq!(e.message, "expected a number");
+    }
+
+    #[test]
+    fn it_reports_wrong_value_count() {
+        let e = parse_error("camera { width 10 20 }");
+        assert_eq!((e.line, e.column), (1, 19));
+        assert_eq!(e.message, "unexpected value");
+    }
+
+    #[test]
+    fn it_reports_unknown_materials() {
+        let e = parse_error("sphere {\n  material chrome\n}");
+        assert_eq!((e.line, e.column), (2, 12));
+        assert_eq!(e.key, "material");
+        assert_eq!(e.message, "unknown material");
+    }
+
+    #[test]
+    fn it_reports_unclosed_blocks() {
+        let e = parse_error("\n\nsphere {\n  translate 0 1 0\n");
+        assert_eq!((e.line, e.column), (3, 1));
+        assert_eq!(e.message, "unclosed `{`");
+    }
+
+    #[test]
+    fn it_reports_unterminated_strings() {
+        let e = parse_error("mesh { file \"teapot.obj }");
+        assert_eq!((e.line, e.column), (1, 13));
+        assert_eq!(e.message, "unterminated string");
+    }
+}
@@ -1,11 +1,41 @@
+use crate::bounds::*;
 use crate::color::*;
 use crate::material::*;
 use crate::ray::*;
 use crate::types::*;
 
-pub trait Shape {
+/// A point picked on the surface of a shape.
+pub struct SurfaceSample {
+    pub point: Point3f,
+    /// The unit normal of the surface itself, even where shading uses another.
+    pub normal: Vec3f,
+    /// The probability density of picking `point`, per unit of world space area.
+    pub pdf: Float,
+}
+
+pub trait Shape: Send + Sync {
     fn intersection(&self, ray: &Ray) -> Option<Float>;
     fn normal_at(&self, world_point: Point3f) -> Vec3f;
     fn color_at(&self, world_point: &Point3f) -> Color;
     fn material(&self) -> &Material;
+    /// World space bounds of the shape, used to accelerate intersection tests.
+    fn bounds(&self) -> BoundingBox;
+
+    /// Roughly how much world space surface the shape has, or 0 if it can't pick points
+    /// on it. Glowing shapes are sampled in proportion to it, so it need not be exact.
+    fn area(&self) -> Float {
+        0.0
+    }
+
+    /// Picks a point on the surface using `(u, v)` in [0, 1)², or None if the shape
+    /// can't, like an infinite plane.
+    fn sample_surface(&self, _u: Float, _v: Float) -> Option<SurfaceSample> {
+        None
+    }
+
+    /// What `sample_surface` would return for `world_point` on the surface, giving the
+    /// density with which it picks that point.
+    fn surface_sample_at(&self, _world_point: &Point3f) -> Option<SurfaceSample> {
+        None
+    }
 }
@@ -2,23 +2,26 @@ use std::f64::consts::*;
 
 use na::*;
 
+use crate::bounds::*;
 use crate::color::*;
 use crate::intersections::*;
 use crate::material::*;
 use crate::model_transformation::*;
+use crate::object_transform::*;
 use crate::ray::*;
+use crate::sampling::*;
 use crate::shape::*;
 use crate::types::*;
 
 pub struct Sphere {
-    pub object_to_world_space: Projective3<Float>,
+    pub object_to_world_space: ObjectTransform,
     pub material: Material,
 }
 
 impl From<ModelTransformation> for Sphere {
     fn from(t: ModelTransformation) -> Self {
         Self {
-            object_to_world_space: t.matrix(),
+            object_to_world_space: t.matrix().into(),
             material: Material::default(),
         }
     }
@@ -26,46 +29,33 @@ impl From<ModelTransformation> for Sphere {
 
 impl Shape for Sphere {
     fn intersection(&self, ray: &Ray) -> Option<Float> {
-        let ray_transformation = self.object_to_world_space.inverse();
-        ray_sphere_intersection(&Ray {
-            origin: ray_transformation * ray.origin,
-            direction: ray_transformation * ray.direction,
-        })
-        .and_then(|(a, b)| {
-            // Pick the minimum intersection not behind the ray.
-            if a >= 0.0 && a < b {
-                Some(a)
-            } else if b >= 0.0 {
-                Some(b)
-            } else {
-                None
-            }
-        })
+        ray_sphere_intersection(&self.object_to_world_space.ray_to_object_space(ray)).and_then(
+            |(a, b)| {
+                // Pick the minimum intersection not behind the ray.
+                if a >= 0.0 && a < b {
+                    Some(a)
+                } else if b >= 0.0 {
+                    Some(b)
+                } else {
+                    None
+                }
+            },
+        )
     }
 
     fn normal_at(&self, world_point: Point3f) -> Vec3f {
-        let object_point = self.object_to_world_space.inverse() * world_point;
-        let object_normal = object_point - Point3::new(0.0, 0.0, 0.0);
-        // We want to invert the scaling component of the object to world space transformation
-        // applied the normal direction vector. The inverse of any rotation matrix is its
-        // transpose. Any matrix composed of rotation and scaling matrices can be orthogonally
-        // diagonalized meaning that the transpose(inv(mat)) will invert the diagonal matrix
-        // (the scaling component) and leave the rotation matrices.
-        let mut world_normal = self
+        let object_point = self
             .object_to_world_space
-            .inverse()
-            .to_homogeneous()
-            .transpose()
-            * Vector4::new(object_normal.x, object_normal.y, object_normal.z, 0.0);
-        // However, matrices with a translation component muck up the orthogonal
-        // diagonalization a bit, so we zero the w component of the normal before
-        // normalizing.
-        world_normal.w = 0.0;
-        world_normal.normalize().xyz()
+            .point_to_object_space(&world_point);
+        let object_normal = object_point - Point3::new(0.0, 0.0, 0.0);
+        self.object_to_world_space
+            .normal_to_world_space(&object_normal)
     }
 
     fn color_at(&self, world_point: &Point3f) -> Color {
-        let object_point = self.object_to_world_space.inverse() * world_point;
+        let object_point = self
+            .object_to_world_space
+            .point_to_object_space(world_point);
         // TODO: UV mapping here does not support general ellipsoids, only spheres
         // (w/ radius 1 on all axes).
         let theta = (-object_point.z).atan2(object_point.x);
@@ -79,6 +69,40 @@ impl Shape for Sphere {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn bounds(&self) -> BoundingBox {
+        self.object_to_world_space
+            .bounds_to_world_space(&BoundingBox::from_points(&[
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, 1.0),
+            ]))
+    }
+
+    fn area(&self) -> Float {
+        4.0 * PI * self.object_to_world_space.mean_area_scale()
+    }
+
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        let object_normal = uniform_sphere(u, v);
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &Point3::from(object_normal),
+            &object_normal,
+            1.0 / (4.0 * PI),
+        ))
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        let object_normal = self
+            .object_to_world_space
+            .point_to_object_space(world_point)
+            .coords
+            .normalize();
+        Some(self.object_to_world_space.surface_sample_to_world_space(
+            &Point3::from(object_normal),
+            &object_normal,
+            1.0 / (4.0 * PI),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -124,7 +148,7 @@ mod tests {
         ))) * Rotation3::from_axis_angle(&Vector3::z_axis(), PI / 5.0)
             * Projective3::identity();
         let sphere = Sphere {
-            object_to_world_space: t,
+            object_to_world_space: t.into(),
             material: Material::default(),
         };
         assert_relative_eq!(
@@ -132,4 +156,36 @@ mod tests {
             Vector3::new(0.0, 0.9701425001453319, -0.24253562503633294)
         );
     }
+
+    #[test]
+    fn it_samples_points_evenly_over_its_surface() {
+        let sphere = Sphere::from(
+            ModelTransformation::new()
+                .translate(1.0, 2.0, 3.0)
+                .scale(2.0, 2.0, 2.0),
+        );
+        assert_relative_eq!(sphere.area(), 16.0 * PI, epsilon = 1e-9);
+        let sample = sphere.sample_surface(0.3, 0.7).unwrap();
+        assert_relative_eq!((sample.point - Point3::new(1.0, 2.0, 3.0)).norm(), 2.0);
+        assert_relative_eq!(sample.normal, sphere.normal_at(sample.point));
+        assert_relative_eq!(sample.pdf, 1.0 / (16.0 * PI), epsilon = 1e-12);
+    }
+
+    #[test]
+    fn it_samples_stretched_spheres_with_varying_density() {
+        let sphere = Sphere::from(ModelTransformation::new().scale(1.0, 3.0, 1.0));
+        // The sides are stretched three times as much as the poles.
+        let pole = sphere
+            .surface_sample_at(&Point3::new(0.0, 3.0, 0.0))
+            .unwrap();
+        let side = sphere
+            .surface_sample_at(&Point3::new(1.0, 0.0, 0.0))
+            .unwrap();
+        assert_relative_eq!(pole.pdf, 1.0 / (4.0 * PI), epsilon = 1e-12);
+        assert_relative_eq!(side.pdf, 1.0 / (12.0 * PI), epsilon = 1e-12);
+        let sample = sphere.sample_surface(0.2, 0.1).unwrap();
+        let again = sphere.surface_sample_at(&sample.point).unwrap();
+        assert_relative_eq!(again.pdf, sample.pdf, epsilon = 1e-12);
+        assert_relative_eq!(again.normal, sample.normal, epsilon = 1e-12);
+    }
 }
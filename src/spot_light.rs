@@ -0,0 +1,141 @@
+use std::f64::consts::*;
+
+use crate::color::*;
+use crate::light::*;
+use crate::rng::*;
+use crate::types::*;
+
+/// Spotlights are at full strength within 30 degrees of their axis and dark beyond 45
+/// unless told otherwise.
+pub const DEFAULT_INNER_ANGLE: Float = FRAC_PI_6;
+pub const DEFAULT_OUTER_ANGLE: Float = FRAC_PI_4;
+
+/// A point light that only shines within a cone around `direction`. It is at full
+/// strength within `inner_angle` of the cone's axis and fades out smoothly toward
+/// `outer_angle`. Both angles are in radians, measured from the axis.
+pub struct SpotLight {
+    pub color: Color,
+    pub position: Point3f,
+    pub direction: Vec3f,
+    pub inner_angle: Float,
+    pub outer_angle: Float,
+    /// Radiant intensity along the axis in watts per steradian, scaling `color`.
+    pub intensity: Float,
+    pub attenuation: Attenuation,
+}
+
+impl SpotLight {
+    /// The solid angle the light shines into, counting the part of the cone it fades
+    /// out over as half lit, so that `power / solid_angle()` gives the intensity a light
+    /// of that power would have.
+    pub fn solid_angle(&self) -> Float {
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        2.0 * PI * ((1.0 - cos_inner) + (cos_inner - cos_outer).max(0.0) / 2.0)
+    }
+
+    /// The fraction of the light's color that reaches points in `direction` from the
+    /// light.
+    fn falloff(&self, direction: &Vec3f) -> Float {
+        let cos_angle = direction.dot(&self.direction.normalize());
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        if cos_inner <= cos_outer {
+            // No room to fade; the cone has a hard edge.
+            return if cos_angle >= cos_outer { 1.0 } else { 0.0 };
+        }
+        let t = ((cos_angle - cos_outer) / (cos_inner - cos_outer)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn samples(&self, point: &Point3f, _rng: &mut Rng) -> Vec<LightSample> {
+        let v = self.position - point;
+        let direction = v.normalize();
+        let falloff = self.falloff(&-direction);
+        if falloff <= 0.0 {
+            return vec![];
+        }
+        let distance = v.norm();
+        vec![LightSample {
+            direction,
+            distance,
+            color: self.color * (self.intensity * falloff * self.attenuation.factor(distance)),
+            pdf: Float::INFINITY,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::*;
+    use na::*;
+
+    use super::*;
+
+    fn spot_light() -> SpotLight {
+        SpotLight {
+            color: Color::new(1.0, 1.0, 1.0),
+            position: Point3::new(0.0, 10.0, 0.0),
+            direction: Vector3::new(0.0, -2.0, 0.0),
+            inner_angle: 0.2,
+            outer_angle: 0.4,
+            intensity: 1.0,
+            attenuation: Attenuation::NONE,
+        }
+    }
+
+    fn color_at(light: &SpotLight, point: Point3f) -> Option<Color> {
+        light
+            .samples(&point, &mut Rng::new(0, 0))
+            .first()
+            .map(|s| s.color)
+    }
+
+    #[test]
+    fn it_shines_fully_inside_inner_cone() {
+        let light = spot_light();
+        let color = color_at(&light, Point3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_relative_eq!(color.0, Vector3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn it_fades_between_cones() {
+        let light = spot_light();
+        // 0.3 radians off the axis is halfway between the cones.
+        let x = 10.0 * (0.3 as Float).tan();
+        let color = color_at(&light, Point3::new(x, 0.0, 0.0)).unwrap();
+        assert!(color.0.x > 0.0 && color.0.x < 1.0);
+        assert!(color_at(&light, Point3::new(0.0, 0.0, 10.0)).is_none());
+        // Behind the light.
+        assert!(color_at(&light, Point3::new(0.0, 20.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn it_measures_solid_angles() {
+        let hemisphere = SpotLight {
+            inner_angle: FRAC_PI_2,
+            outer_angle: FRAC_PI_2,
+            ..spot_light()
+        };
+        assert_relative_eq!(hemisphere.solid_angle(), 2.0 * PI);
+        let fading = SpotLight {
+            inner_angle: 0.0,
+            outer_angle: PI,
+            ..spot_light()
+        };
+        assert_relative_eq!(fading.solid_angle(), 2.0 * PI);
+    }
+
+    #[test]
+    fn it_has_hard_edges_without_room_to_fade() {
+        let light = SpotLight {
+            inner_angle: 0.4,
+            ..spot_light()
+        };
+        let inside = 10.0 * (0.39 as Float).tan();
+        let outside = 10.0 * (0.41 as Float).tan();
+        let color = color_at(&light, Point3::new(inside, 0.0, 0.0)).unwrap();
+        assert_relative_eq!(color.0, Vector3::new(1.0, 1.0, 1.0));
+        assert!(color_at(&light, Point3::new(outside, 0.0, 0.0)).is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use crate::color::*;
+use crate::types::*;
+
+/// Maps scene radiance, which can be arbitrarily bright, into the [0, 1] range a display
+/// can show.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Clips anything brighter than 1.
+    Clamp,
+    /// L / (1 + L), applied to luminance so hues are kept.
+    Reinhard,
+    /// Reinhard with a white point: luminance at the white point maps to exactly 1. Without
+    /// one, the brightest pixel of the image is used.
+    ExtendedReinhard { white_point: Option<Float> },
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve.
+    ///
+    /// <https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/>
+    Aces,
+}
+
+/// The post-process stage between a rendered Image and the writers of low dynamic range
+/// formats: exposure, then tone mapping, then the sRGB transfer function.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapper {
+    pub operator: ToneMapping,
+    /// In stops; each one doubles the radiance.
+    pub exposure: Float,
+    /// Whether to apply the sRGB transfer function. Images of data rather than radiance,
+    /// such as sample counts, leave it off so that their values are stored linearly.
+    pub srgb: bool,
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        ToneMapper {
+            operator: ToneMapping::Clamp,
+            exposure: 0.0,
+            srgb: true,
+        }
+    }
+}
+
+impl ToneMapper {
+    /// Produces colors with members in [0, 1], sRGB encoded unless `srgb` is off.
+    #[allow(clippy::ptr_arg)]
+    pub fn apply(&self, image: &Image) -> Image {
+        let scale = Float::powf(2.0, self.exposure);
+        let operator = match self.operator {
+            ToneMapping::ExtendedReinhard { white_point: None } => {
+                let brightest = image
+                    .iter()
+                    .flatten()
+                    .map(|c| c.luminance() * scale)
+                    .fold(0.0, Float::max);
+                ToneMapping::ExtendedReinhard {
+                    white_point: Some(brightest),
+                }
+            }
+            operator => operator,
+        };
+
+        image
+            .iter()
+            .map(|col| {
+                col.iter()
+                    .map(|&c| {
+                        let mapped = tone_map(operator, c * scale);
+                        if self.srgb {
+                            encode_srgb(mapped)
+                        } else {
+                            mapped
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn tone_map(operator: ToneMapping, color: Color) -> Color {
+    match operator {
+        ToneMapping::Clamp => color,
+        ToneMapping::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+        ToneMapping::ExtendedReinhard { white_point } => {
+            let white_squared = white_point.map_or(1.0, |w| (w * w).max(EPSILON));
+            scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+        }
+        ToneMapping::Aces => {
+            let Color(v) = color;
+            let curve = |x: Float| {
+                let x = x.max(0.0);
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            };
+            Color::new(curve(v.x), curve(v.y), curve(v.z))
+        }
+    }
+    .clamp()
+}
+
+fn scale_luminance<F>(color: Color, curve: F) -> Color
+where
+    F: Fn(Float) -> Float,
+{
+    let luminance = color.luminance();
+    if luminance <= 0.0 {
+        return Color::new(0.0, 0.0, 0.0);
+    }
+    color * (curve(luminance) / luminance)
+}
+
+/// The sRGB transfer function, from linear [0, 1] to encoded [0, 1].
+pub fn encode_srgb(color: Color) -> Color {
+    let Color(v) = color;
+    let encode = |c: Float| {
+        if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    Color::new(encode(v.x), encode(v.y), encode(v.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    fn gray(c: Float) -> Color {
+        Color::new(c, c, c)
+    }
+
+    #[test]
+    fn it_encodes_srgb() {
+        assert_relative_eq!(encode_srgb(gray(0.0)).0.x, 0.0);
+        assert_relative_eq!(encode_srgb(gray(0.001)).0.x, 0.01292);
+        assert_relative_eq!(encode_srgb(gray(0.5)).0.x, 0.735356, epsilon = 1e-6);
+        assert_relative_eq!(encode_srgb(gray(1.0)).0.x, 1.0, epsilon = 1e-12);
+    }
+
+    #[test]
+    fn it_maps_with_each_operator() {
+        assert_eq!(tone_map(ToneMapping::Clamp, gray(4.0)), gray(1.0));
+        assert_relative_eq!(tone_map(ToneMapping::Reinhard, gray(1.0)).0.x, 0.5);
+        assert_relative_eq!(
+            tone_map(
+                ToneMapping::ExtendedReinhard {
+                    white_point: Some(4.0)
+                },
+                gray(4.0)
+            )
+            .0
+            .x,
+            1.0
+        );
+        assert_relative_eq!(
+            tone_map(ToneMapping::Aces, gray(1.0)).0.x,
+            2.54 / 3.16,
+            epsilon = 1e-12
+        );
+        assert_eq!(tone_map(ToneMapping::Aces, gray(0.0)), gray(0.0));
+    }
+
+    #[test]
+    fn it_keeps_hue_with_reinhard() {
+        let Color(v) = tone_map(ToneMapping::Reinhard, Color::new(2.0, 1.0, 0.0));
+        assert_relative_eq!(v.x / v.y, 2.0, epsilon = 1e-12);
+        assert_relative_eq!(v.z, 0.0);
+    }
+
+    #[test]
+    fn it_applies_exposure_and_brightest_white_point() {
+        let image = vec![vec![gray(0.25), gray(1.0)]];
+        let tone_mapper = ToneMapper {
+            operator: ToneMapping::ExtendedReinhard { white_point: None },
+            exposure: 1.0,
+            srgb: true,
+        };
+        let mapped = tone_mapper.apply(&image);
+        assert_relative_eq!(mapped[0][1].0.x, 1.0, epsilon = 1e-12);
+        // 0.5 under a white point of 2 maps to 0.5 * 1.125 / 1.5.
+        assert_relative_eq!(
+            mapped[0][0].0.x,
+            encode_srgb(gray(0.375)).0.x,
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn it_leaves_values_linear_without_srgb() {
+        let tone_mapper = ToneMapper {
+            srgb: false,
+            ..ToneMapper::default()
+        };
+        let mapped = tone_mapper.apply(&vec![vec![gray(0.25), gray(2.0)]]);
+        assert_eq!(mapped, vec![vec![gray(0.25), gray(1.0)]]);
+    }
+}
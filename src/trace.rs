@@ -1,18 +1,31 @@
-use crate::camera::*;
+use std::f64::consts::*;
+
 use crate::color::*;
+use crate::light::*;
 use crate::material::*;
-use crate::point_light::*;
 use crate::ray::*;
+use crate::rng::*;
 use crate::scene::*;
 use crate::shape::*;
 use crate::types::*;
 
-pub fn render(camera: &Camera, scene: &Scene, x: u32, y: u32) -> Color {
-    let primary_ray = camera.ray_for_pixel(x, y);
-    trace(scene, &primary_ray)
+/// Renders along a primary ray leaving the camera. `rng` chooses the shadow rays cast
+/// toward area lights.
+pub fn render_ray(scene: &Scene, primary_ray: &Ray, rng: &mut Rng) -> Color {
+    // The camera is assumed to sit in air, outside of every object.
+    trace(scene, primary_ray, scene.max_depth, &[], rng)
 }
 
-fn trace(scene: &Scene, ray: &Ray) -> Color {
+/// `remaining_depth` bounds the number of secondary rays spawned from this one, so that
+/// facing mirrors do not recurse forever. `containers` is the stack of objects the ray is
+/// currently travelling through, innermost last.
+fn trace<'a>(
+    scene: &'a Scene,
+    ray: &Ray,
+    remaining_depth: u32,
+    containers: &[&'a Shape],
+    rng: &mut Rng,
+) -> Color {
     match scene
         .intersection(&ray)
         .map(|(t, obj)| (ray.point_at(t), obj))
@@ -24,75 +37,242 @@ fn trace(scene: &Scene, ray: &Ray) -> Color {
             &intersection_point,
             &ray,
             obj.normal_at(intersection_point),
-        )
-        .clamp(),
-        None => Color::new(0.0, 0.0, 0.0),
+            containers,
+            remaining_depth,
+            rng,
+        ),
+        None => scene
+            .environment
+            .as_ref()
+            .map_or(Color::new(0.0, 0.0, 0.0), |e| e.radiance(&ray.direction)),
     }
 }
 
-fn shade_intersection(
-    scene: &Scene,
-    shape: &Shape,
-    lights: &[PointLight],
+#[allow(clippy::too_many_arguments)]
+fn shade_intersection<'a>(
+    scene: &'a Scene,
+    shape: &'a Shape,
+    lights: &[Box<Light>],
     point: &Point3f,
     incoming_ray: &Ray,
     normal: Vec3f,
+    containers: &[&'a Shape],
+    remaining_depth: u32,
+    rng: &mut Rng,
 ) -> Color {
     let ambient = shape.color_at(point) * shape.material().ambient;
+    // Glowing surfaces are seen, but only the path tracer lights other surfaces with
+    // them.
+    let emitted = shape.material().emitted();
     let corrected_normal = if incoming_ray.is_inside(&normal) {
         -normal
     } else {
         normal
     };
-    ambient
-        + lights
-            .iter()
-            .fold(Color::new(0.0, 0.0, 0.0), |total, light| {
-                total
-                    + light_contribution(
-                        scene,
-                        shape,
-                        light,
-                        point,
-                        incoming_ray,
-                        &corrected_normal,
-                    )
-            })
+    let direct = lights
+        .iter()
+        .fold(Color::new(0.0, 0.0, 0.0), |total, light| {
+            total
+                + light_contribution(
+                    scene,
+                    shape,
+                    light.as_ref(),
+                    point,
+                    incoming_ray,
+                    &corrected_normal,
+                    rng,
+                )
+        });
+    // The environment's samples carry irradiance, which is π times the radiance a white
+    // matte surface reflects, while Whitted shading takes the other lights' samples as
+    // that radiance.
+    let environment = scene
+        .environment
+        .as_ref()
+        .map_or(Color::new(0.0, 0.0, 0.0), |e| {
+            light_contribution(scene, shape, e, point, incoming_ray, &corrected_normal, rng)
+                * (1.0 / PI)
+        });
+    let surface = ambient + emitted + direct + environment;
+    let reflected = reflected_color(
+        scene,
+        shape,
+        point,
+        incoming_ray,
+        &corrected_normal,
+        containers,
+        remaining_depth,
+        rng,
+    );
+
+    let (n1, n2, refracted_containers) = refractive_indices(containers, shape);
+    let refracted = refracted_color(
+        scene,
+        shape,
+        point,
+        incoming_ray,
+        &corrected_normal,
+        n1 / n2,
+        &refracted_containers,
+        remaining_depth,
+        rng,
+    );
+
+    let material = shape.material();
+    if material.reflective > 0.0 && material.transparency > 0.0 {
+        let reflectance = schlick(&incoming_ray.direction, &corrected_normal, n1, n2);
+        surface + reflected * reflectance + refracted * (1.0 - reflectance)
+    } else {
+        surface + reflected + refracted
+    }
 }
 
-fn light_contribution(
-    scene: &Scene,
+fn is_same_shape(a: &Shape, b: &Shape) -> bool {
+    // Compare data pointers only; vtable pointers for the same type are not guaranteed to
+    // be unique.
+    std::ptr::eq(
+        std::ptr::from_ref(a).cast::<u8>(),
+        std::ptr::from_ref(b).cast::<u8>(),
+    )
+}
+
+/// Determines the refractive index on either side of the surface of `shape` and the
+/// containers a ray refracted through that surface will be inside of. Hitting a shape we
+/// are already inside of means we are leaving it.
+pub fn refractive_indices<'a>(
+    containers: &[&'a Shape],
+    shape: &'a Shape,
+) -> (Float, Float, Vec<&'a Shape>) {
+    let index_of = |c: &[&Shape]| c.last().map_or(1.0, |obj| obj.material().refractive_index);
+    let n1 = index_of(containers);
+
+    let mut refracted_containers = containers.to_vec();
+    match refracted_containers
+        .iter()
+        .position(|obj| is_same_shape(*obj, shape))
+    {
+        Some(i) => {
+            refracted_containers.remove(i);
+        }
+        None => refracted_containers.push(shape),
+    }
+    let n2 = index_of(&refracted_containers);
+
+    (n1, n2, refracted_containers)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reflected_color<'a>(
+    scene: &'a Scene,
     shape: &Shape,
-    light: &PointLight,
     point: &Point3f,
     incoming_ray: &Ray,
     normal: &Vec3f,
+    containers: &[&'a Shape],
+    remaining_depth: u32,
+    rng: &mut Rng,
 ) -> Color {
-    let (distance, shadow_direction) = light.direction_from(point);
-    let shadow_ray = Ray {
-        origin: *point + normal * BIAS,
-        direction: shadow_direction,
-    };
-    if scene.is_occluded(&shadow_ray, distance) {
+    let reflective = shape.material().reflective;
+    if reflective <= 0.0 || remaining_depth == 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
 
-    let effective_color = shape.color_at(point).mix(light.color);
+    let reflected_ray = Ray {
+        origin: *point + normal * BIAS,
+        direction: reflect(&incoming_ray.direction, normal),
+    };
+    trace(scene, &reflected_ray, remaining_depth - 1, containers, rng) * reflective
+}
 
-    let facing_ratio = shadow_direction.dot(&normal);
-    if facing_ratio < 0.0 {
+#[allow(clippy::too_many_arguments)]
+fn refracted_color<'a>(
+    scene: &'a Scene,
+    shape: &Shape,
+    point: &Point3f,
+    incoming_ray: &Ray,
+    normal: &Vec3f,
+    refraction_ratio: Float,
+    refracted_containers: &[&'a Shape],
+    remaining_depth: u32,
+    rng: &mut Rng,
+) -> Color {
+    let transparency = shape.material().transparency;
+    if transparency <= 0.0 || remaining_depth == 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
 
-    let diffuse = effective_color * shape.material().diffuse * facing_ratio;
-    let specular = compute_reflection(
-        shape.material(),
-        light,
-        incoming_ray,
-        normal,
-        &shadow_direction,
-    );
-    diffuse + specular
+    match refract(&incoming_ray.direction, normal, refraction_ratio) {
+        Some(direction) => {
+            // The refracted ray continues on the far side of the surface, so it must be
+            // biased against the normal rather than along it.
+            let refracted_ray = Ray {
+                origin: *point - normal * BIAS,
+                direction,
+            };
+            trace(
+                scene,
+                &refracted_ray,
+                remaining_depth - 1,
+                refracted_containers,
+                rng,
+            ) * transparency
+        }
+        // Total internal reflection; all of the light is accounted for by reflection.
+        None => Color::new(0.0, 0.0, 0.0),
+    }
+}
+
+/// Sums the diffuse and specular light reaching `point` through each of the light's
+/// shadow rays that is not blocked. Microfacet materials use their BSDF instead of the
+/// Phong terms, scaled by π since Whitted shading leaves out the 1/π of a Lambertian
+/// surface.
+fn light_contribution(
+    scene: &Scene,
+    shape: &Shape,
+    light: &Light,
+    point: &Point3f,
+    incoming_ray: &Ray,
+    normal: &Vec3f,
+    rng: &mut Rng,
+) -> Color {
+    let surface_color = shape.color_at(point);
+    let material = shape.material();
+    let bsdf = material
+        .microfacet
+        .map(|_| material.bsdf(surface_color, *normal));
+    light
+        .samples(point, rng)
+        .iter()
+        .fold(Color::new(0.0, 0.0, 0.0), |total, sample| {
+            let facing_ratio = sample.direction.dot(normal);
+            if facing_ratio < 0.0 {
+                return total;
+            }
+
+            let shadow_ray = Ray {
+                origin: *point + normal * BIAS,
+                direction: sample.direction,
+            };
+            if scene.is_occluded(&shadow_ray, sample.distance) {
+                return total;
+            }
+
+            if let Some(bsdf) = &bsdf {
+                let outgoing = incoming_ray.direction_to_origin().normalize();
+                let value = bsdf.eval(&outgoing, &sample.direction);
+                return total + value.mix(sample.color) * (PI * facing_ratio);
+            }
+
+            let diffuse = surface_color.mix(sample.color) * material.diffuse * facing_ratio;
+            let specular = compute_reflection(
+                material,
+                sample.color,
+                incoming_ray,
+                normal,
+                &sample.direction,
+            );
+            total + diffuse + specular
+        })
 }
 
 /// Returns None if the shadow ray is being reflected away from the eye. Otherwise this
@@ -109,7 +289,7 @@ fn reflection_ratio(incoming_ray: &Ray, normal: &Vec3f, shadow_direction: &Vec3f
 
 fn compute_reflection(
     material: &Material,
-    light: &PointLight,
+    light_color: Color,
     incoming_ray: &Ray,
     normal: &Vec3f,
     shadow_direction: &Vec3f,
@@ -120,7 +300,7 @@ fn compute_reflection(
     };
 
     let factor = ratio.powf(material.shininess);
-    light.color * material.specular * factor
+    light_color * material.specular * factor
 }
 
 #[cfg(test)]
@@ -131,13 +311,18 @@ mod tests {
     use na::*;
 
     use super::*;
+    use crate::environment_light::*;
+    use crate::pattern::*;
+    use crate::plane::*;
+    use crate::point_light::*;
     use crate::sphere::*;
 
     fn empty_scene() -> Scene {
-        Scene {
-            objects: vec![],
-            lights: vec![],
-        }
+        Scene::new()
+    }
+
+    fn boxed(light: PointLight) -> Box<Light> {
+        Box::new(light)
     }
 
     #[test]
@@ -145,22 +330,27 @@ mod tests {
         let light = PointLight {
             color: Color::new(1.0, 1.0, 1.0),
             position: Point3::new(0.0, 0.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
         };
         let r = Ray {
             origin: Point3::new(0.0, 0.0, -5.0),
             direction: Vector3::new(0.0, 0.0, 1.0),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &Point3::new(0.0, 0.0, 0.0),
             &r,
             Vector3::new(0.0, 0.0, -1.0),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         assert_relative_eq!(color.0, Vector3::new(1.9, 1.9, 1.9));
     }
@@ -170,6 +360,8 @@ mod tests {
         let light = PointLight {
             color: Color::new(1.0, 1.0, 1.0),
             position: Point3::new(0.0, 0.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
         };
         // The normal is pointing directly at the light source with the eye observing from
         // a 45 degree offset above and between the point and light. There should be zero
@@ -179,26 +371,88 @@ mod tests {
             direction: Vector3::new(0.0, FRAC_PI_4.sin(), FRAC_PI_4.sin()),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &Point3::new(0.0, 0.0, 0.0),
             &r,
             Vector3::new(0.0, 0.0, -1.0),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         assert_relative_eq!(color.0, Vector3::new(1.0, 1.0, 1.0));
     }
 
+    #[test]
+    fn it_computes_lighting_for_microfacet_materials() {
+        let light = PointLight {
+            position: Point3::new(0.0, 0.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
+        };
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let shade = |microfacet| {
+            let s = Sphere {
+                object_to_world_space: Projective3::identity().into(),
+                material: Material {
+                    color: Box::new(SolidPattern(Color::new(1.0, 0.8, 0.3))),
+                    ambient: 0.0,
+                    microfacet: Some(microfacet),
+                    ..Material::default()
+                },
+            };
+            shade_intersection(
+                &empty_scene(),
+                &s,
+                &[boxed(PointLight { ..light })],
+                &Point3::new(0.0, 0.0, -1.0),
+                &r,
+                Vector3::new(0.0, 0.0, -1.0),
+                &[],
+                0,
+                &mut Rng::new(0, 0),
+            )
+        };
+
+        // Metals reflect their base color head on, and smoother ones more sharply.
+        let metal = shade(Microfacet {
+            roughness: 0.3,
+            metallic: 1.0,
+            ..Microfacet::default()
+        });
+        assert_relative_eq!(metal.0.y / metal.0.x, 0.8, epsilon = 1e-12);
+        assert_relative_eq!(metal.0.z / metal.0.x, 0.3, epsilon = 1e-12);
+        let polished = shade(Microfacet {
+            roughness: 0.1,
+            metallic: 1.0,
+            ..Microfacet::default()
+        });
+        assert!(polished.0.x > metal.0.x * 5.0);
+
+        // A rough dielectric is close to the Lambertian diffuse term.
+        let rough = shade(Microfacet {
+            roughness: 1.0,
+            ..Microfacet::default()
+        });
+        assert_relative_eq!(rough.0, Vector3::new(1.0, 0.8, 0.3), epsilon = 0.1);
+    }
+
     #[test]
     fn it_computes_lighting_with_diffuse_falloff() {
         let light = PointLight {
             color: Color::new(1.0, 1.0, 1.0),
             // 45 degree offset from point.
             position: Point3::new(0.0, 10.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
         };
         // The normal is pointing directly at the eye but 45 degrees from the light source.
         let r = Ray {
@@ -206,16 +460,19 @@ mod tests {
             direction: Vector3::new(0.0, 0.0, 1.0),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &Point3::new(0.0, 0.0, 0.0),
             &r,
             Vector3::new(0.0, 0.0, -1.0),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         let k = 0.1 + 0.9 * FRAC_PI_4.sin();
         assert_relative_eq!(color.0, Vector3::new(k, k, k));
@@ -227,6 +484,8 @@ mod tests {
             color: Color::new(1.0, 1.0, 1.0),
             // 45 degree offset from point.
             position: Point3::new(0.0, 10.0, -10.0),
+            attenuation: Attenuation::NONE,
+            ..PointLight::default()
         };
         // The light source and eye are 90 degrees apart and each are 45 degrees from the
         // normal.
@@ -235,16 +494,19 @@ mod tests {
             direction: Vector3::new(0.0, FRAC_PI_4.sin(), FRAC_PI_4.sin()),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &Point3::new(0.0, 0.0, 0.0),
             &r,
             Vector3::new(0.0, 0.0, -1.0),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         let k = 0.1 + 0.9 + 0.9 * FRAC_PI_4.sin();
         // Unfortunately this test suffers a liiiiitle more floating point error than
@@ -262,22 +524,26 @@ mod tests {
             color: Color::new(1.0, 1.0, 1.0),
             // The light is positioned behind the point.
             position: Point3::new(0.0, 0.0, 10.0),
+            ..PointLight::default()
         };
         let r = Ray {
             origin: Point3::new(0.0, 0.0, -5.0),
             direction: Vector3::new(0.0, 0.0, 1.0),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &Point3::new(0.0, 0.0, 0.0),
             &r,
             Vector3::new(0.0, 0.0, -1.0),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         assert_relative_eq!(color.0, Vector3::new(0.1, 0.1, 0.1));
     }
@@ -287,24 +553,346 @@ mod tests {
         let light = PointLight {
             color: Color::new(1.0, 1.0, 1.0),
             position: Point3::new(0.0, 0.0, 0.0),
+            ..PointLight::default()
         };
         let r = Ray {
             origin: Point3::new(0.0, 0.0, 0.0),
             direction: Vector3::new(0.0, 0.0, 1.0),
         };
         let s = Sphere {
-            object_to_world_space: Projective3::identity(),
+            object_to_world_space: Projective3::identity().into(),
             material: Default::default(),
         };
         let point = s.intersection(&r).map(|t| r.point_at(t)).unwrap();
         let color = shade_intersection(
             &empty_scene(),
             &s,
-            &vec![light],
+            &[boxed(light)],
             &point,
             &r,
             s.normal_at(point),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
         );
         assert_relative_eq!(color.0, Vector3::new(1.9, 1.9, 1.9));
     }
+
+    #[test]
+    fn it_computes_no_reflection_for_non_reflective_material() {
+        let mut scene = empty_scene();
+        scene
+            .objects
+            .push(Box::new(Plane::floor(-1.0, Material::default())));
+        let s = Plane::floor(0.0, Material::default());
+        let k = FRAC_PI_4.sin();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -k, k),
+        };
+        let color = reflected_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            &[],
+            5,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_computes_reflection_off_reflective_material() {
+        let mut scene = empty_scene();
+        // A white ceiling lit only by its ambient term is all the mirror can see.
+        scene.objects.push(Box::new(Plane::ceiling(
+            3.0,
+            Material {
+                color: Box::new(SolidPattern(Color::new(1.0, 1.0, 1.0))),
+                ambient: 1.0,
+                ..Material::default()
+            },
+        )));
+        let s = Plane::floor(
+            0.0,
+            Material {
+                reflective: 0.5,
+                ..Material::default()
+            },
+        );
+        let k = FRAC_PI_4.sin();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -k, k),
+        };
+        let color = reflected_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            &[],
+            5,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn it_sees_glowing_surfaces() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(Plane::ceiling(
+            3.0,
+            Material {
+                ambient: 0.0,
+                emission: Color::new(1.0, 0.5, 0.25),
+                emission_strength: 2.0,
+                ..Material::default()
+            },
+        )));
+        scene.objects.push(Box::new(Plane::floor(
+            0.0,
+            Material {
+                ambient: 0.0,
+                diffuse: 0.0,
+                reflective: 0.5,
+                ..Material::default()
+            },
+        )));
+        scene.build_bvh();
+        let k = FRAC_PI_4.sin();
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -k, k),
+        };
+        let color = render_ray(&scene, &r, &mut Rng::new(0, 0));
+        assert_relative_eq!(color.0, Vector3::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn it_stops_reflecting_at_max_depth() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(Plane::ceiling(
+            3.0,
+            Material {
+                ambient: 1.0,
+                ..Material::default()
+            },
+        )));
+        let s = Plane::floor(
+            0.0,
+            Material {
+                reflective: 0.5,
+                ..Material::default()
+            },
+        );
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -1.0, 1.0).normalize(),
+        };
+        let color = reflected_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            &[],
+            0,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_terminates_between_facing_mirrors() {
+        let mirror = || Material {
+            reflective: 1.0,
+            ..Material::default()
+        };
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(Plane::floor(-1.0, mirror())));
+        scene.objects.push(Box::new(Plane::ceiling(1.0, mirror())));
+        scene.lights.push(Box::new(PointLight {
+            color: Color::new(1.0, 1.0, 1.0),
+            position: Point3::new(0.0, 0.0, 0.0),
+            ..PointLight::default()
+        }));
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, 0.0),
+            direction: Vector3::y(),
+        };
+        // Every hit is straight above or below the light, so each adds the full ambient,
+        // diffuse and specular terms, and the last one is at the depth limit.
+        let color = trace(&scene, &r, DEFAULT_MAX_DEPTH, &[], &mut Rng::new(0, 0));
+        let expected = (0.1 + 0.9 + 0.9) * Float::from(DEFAULT_MAX_DEPTH + 1);
+        assert_relative_eq!(color.0, Vector3::new(expected, expected, expected));
+    }
+
+    #[test]
+    fn it_lights_matte_surfaces_with_the_environment_alone() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(Sphere {
+            object_to_world_space: Projective3::identity().into(),
+            material: Material {
+                ambient: 0.0,
+                specular: 0.0,
+                ..Material::default()
+            },
+        }));
+        let sky = vec![vec![Color::new(1.0, 1.0, 1.0); 16]; 32];
+        scene.environment = Some(EnvironmentLight::new(sky, 1.0, 0.0, 1024));
+        let r = Ray {
+            origin: Point3::new(0.0, 0.0, -5.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        // Under a uniform sky of radiance 1, a matte surface reflects its diffuse albedo.
+        let color = trace(&scene, &r, 0, &[], &mut Rng::new(0, 0));
+        assert_relative_eq!(color.0, Vector3::new(0.9, 0.9, 0.9), epsilon = 0.05);
+    }
+
+    fn glass(refractive_index: Float) -> Material {
+        Material {
+            transparency: 1.0,
+            refractive_index,
+            ..Material::default()
+        }
+    }
+
+    fn glowing_floor(y: Float) -> Plane {
+        Plane::floor(
+            y,
+            Material {
+                ambient: 1.0,
+                ..Material::default()
+            },
+        )
+    }
+
+    #[test]
+    fn it_tracks_refractive_indices_through_nested_shapes() {
+        let outer = Sphere {
+            object_to_world_space: Projective3::identity().into(),
+            material: glass(1.5),
+        };
+        let inner = Sphere {
+            object_to_world_space: Projective3::identity().into(),
+            material: glass(2.0),
+        };
+
+        let (n1, n2, containers) = refractive_indices(&[], &outer);
+        assert_relative_eq!(n1, 1.0);
+        assert_relative_eq!(n2, 1.5);
+        let (n1, n2, containers) = refractive_indices(&containers, &inner);
+        assert_relative_eq!(n1, 1.5);
+        assert_relative_eq!(n2, 2.0);
+        let (n1, n2, containers) = refractive_indices(&containers, &inner);
+        assert_relative_eq!(n1, 2.0);
+        assert_relative_eq!(n2, 1.5);
+        let (n1, n2, containers) = refractive_indices(&containers, &outer);
+        assert_relative_eq!(n1, 1.5);
+        assert_relative_eq!(n2, 1.0);
+        assert!(containers.is_empty());
+    }
+
+    #[test]
+    fn it_computes_no_refraction_for_opaque_material() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(glowing_floor(-1.0)));
+        let s = Plane::floor(0.0, Material::default());
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        let color = refracted_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            1.0,
+            &[],
+            5,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_computes_refraction_through_transparent_material() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(glowing_floor(-1.0)));
+        let s = Plane::floor(
+            0.0,
+            Material {
+                transparency: 0.5,
+                ..Material::default()
+            },
+        );
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        let color = refracted_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            1.0,
+            &[],
+            5,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn it_computes_no_refraction_under_total_internal_reflection() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(glowing_floor(-1.0)));
+        let s = Plane::floor(0.0, glass(1.5));
+        let k = FRAC_PI_4.sin();
+        // Leaving the glass at 45 degrees exceeds its critical angle.
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, -1.0),
+            direction: Vector3::new(0.0, -k, k),
+        };
+        let color = refracted_color(
+            &scene,
+            &s,
+            &Point3::new(0.0, 0.0, 0.0),
+            &r,
+            &Vector3::y(),
+            1.5,
+            &[],
+            5,
+            &mut Rng::new(0, 0),
+        );
+        assert_relative_eq!(color.0, Vector3::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn it_blends_reflection_and_refraction_with_schlick() {
+        let mut scene = empty_scene();
+        scene.objects.push(Box::new(Plane::floor(
+            0.0,
+            Material {
+                reflective: 0.5,
+                transparency: 0.5,
+                refractive_index: 1.5,
+                ..Material::default()
+            },
+        )));
+        scene.objects.push(Box::new(glowing_floor(-1.0)));
+        let r = Ray {
+            origin: Point3::new(0.0, 1.0, 0.0),
+            direction: Vector3::new(0.0, -1.0, 0.0),
+        };
+        let color = trace(&scene, &r, scene.max_depth, &[], &mut Rng::new(0, 0));
+        // Ambient + nothing reflected + 96% of the transmitted half of the glowing floor.
+        let k = 0.1 + 0.5 * 0.96;
+        assert_relative_eq!(color.0, Vector3::new(k, k, k));
+    }
 }
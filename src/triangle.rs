@@ -0,0 +1,253 @@
+use crate::bounds::*;
+use crate::color::*;
+use crate::intersections::*;
+use crate::material::*;
+use crate::ray::*;
+use crate::shape::*;
+use crate::types::*;
+
+/// A flat triangle defined directly in world space by its three vertices.
+pub struct Triangle {
+    pub p1: Point3f,
+    pub p2: Point3f,
+    pub p3: Point3f,
+    pub material: Material,
+    e1: Vec3f,
+    e2: Vec3f,
+    normal: Vec3f,
+}
+
+impl Triangle {
+    pub fn new(p1: Point3f, p2: Point3f, p3: Point3f, material: Material) -> Self {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        Self {
+            p1,
+            p2,
+            p3,
+            material,
+            e1,
+            e2,
+            normal: e2.cross(&e1).normalize(),
+        }
+    }
+
+    /// Computes the barycentric coordinates (u, v) of a point in the plane of the triangle,
+    /// weighting the second and third vertices respectively.
+    pub fn barycentric(&self, point: &Point3f) -> (Float, Float) {
+        let p = point - self.p1;
+        let d00 = self.e1.dot(&self.e1);
+        let d01 = self.e1.dot(&self.e2);
+        let d11 = self.e2.dot(&self.e2);
+        let d20 = p.dot(&self.e1);
+        let d21 = p.dot(&self.e2);
+        let denominator = d00 * d11 - d01 * d01;
+        (
+            (d11 * d20 - d01 * d21) / denominator,
+            (d00 * d21 - d01 * d20) / denominator,
+        )
+    }
+}
+
+impl Shape for Triangle {
+    fn intersection(&self, ray: &Ray) -> Option<Float> {
+        ray_triangle_intersection(ray, &self.p1, &self.e1, &self.e2).and_then(|(t, _, _)| {
+            if t >= EPSILON {
+                Some(t)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn normal_at(&self, _: Point3f) -> Vec3f {
+        self.normal
+    }
+
+    fn color_at(&self, world_point: &Point3f) -> Color {
+        self.material.color.color_at(*world_point)
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        BoundingBox::from_points(&[self.p1, self.p2, self.p3])
+    }
+
+    fn area(&self) -> Float {
+        self.e1.cross(&self.e2).norm() / 2.0
+    }
+
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        // Folding the square onto the triangle by its square root keeps points evenly
+        // spread.
+        let root = u.sqrt();
+        Some(SurfaceSample {
+            point: self.p1 + self.e1 * (root * (1.0 - v)) + self.e2 * (root * v),
+            normal: self.normal,
+            pdf: 1.0 / self.area(),
+        })
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        Some(SurfaceSample {
+            point: *world_point,
+            normal: self.normal,
+            pdf: 1.0 / self.area(),
+        })
+    }
+}
+
+/// A triangle whose normal is interpolated from per-vertex normals, so that a mesh of them
+/// appears smoothly curved.
+pub struct SmoothTriangle {
+    pub triangle: Triangle,
+    pub n1: Vec3f,
+    pub n2: Vec3f,
+    pub n3: Vec3f,
+}
+
+impl SmoothTriangle {
+    pub fn new(vertices: [Point3f; 3], normals: [Vec3f; 3], material: Material) -> Self {
+        let [p1, p2, p3] = vertices;
+        let [n1, n2, n3] = normals;
+        Self {
+            triangle: Triangle::new(p1, p2, p3, material),
+            n1,
+            n2,
+            n3,
+        }
+    }
+}
+
+impl Shape for SmoothTriangle {
+    fn intersection(&self, ray: &Ray) -> Option<Float> {
+        self.triangle.intersection(ray)
+    }
+
+    fn normal_at(&self, world_point: Point3f) -> Vec3f {
+        let (u, v) = self.triangle.barycentric(&world_point);
+        (self.n2 * u + self.n3 * v + self.n1 * (1.0 - u - v)).normalize()
+    }
+
+    fn color_at(&self, world_point: &Point3f) -> Color {
+        self.triangle.color_at(world_point)
+    }
+
+    fn material(&self) -> &Material {
+        &self.triangle.material
+    }
+
+    fn bounds(&self) -> BoundingBox {
+        self.triangle.bounds()
+    }
+
+    fn area(&self) -> Float {
+        self.triangle.area()
+    }
+
+    fn sample_surface(&self, u: Float, v: Float) -> Option<SurfaceSample> {
+        self.triangle.sample_surface(u, v)
+    }
+
+    fn surface_sample_at(&self, world_point: &Point3f) -> Option<SurfaceSample> {
+        self.triangle.surface_sample_at(world_point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use na::*;
+
+    use super::*;
+
+    fn triangle() -> Triangle {
+        Triangle::new(
+            Point3::new(0.0, 1.0, 0.0),
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Material::default(),
+        )
+    }
+
+    fn smooth_triangle() -> SmoothTriangle {
+        SmoothTriangle::new(
+            [
+                Point3::new(0.0, 1.0, 0.0),
+                Point3::new(-1.0, 0.0, 0.0),
+                Point3::new(1.0, 0.0, 0.0),
+            ],
+            [
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(-1.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+            ],
+            Material::default(),
+        )
+    }
+
+    #[test]
+    fn it_computes_constant_normal() {
+        let t = triangle();
+        assert_relative_eq!(
+            t.normal_at(Point3::new(0.0, 0.5, 0.0)),
+            Vector3::new(0.0, 0.0, -1.0)
+        );
+        assert_relative_eq!(
+            t.normal_at(Point3::new(0.5, 0.25, 0.0)),
+            Vector3::new(0.0, 0.0, -1.0)
+        );
+    }
+
+    #[test]
+    fn it_intersects_in_front_of_ray_only() {
+        let t = triangle();
+        let r = Ray {
+            origin: Point3::new(0.0, 0.5, -2.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert_relative_eq!(t.intersection(&r).unwrap(), 2.0);
+        let r = Ray {
+            origin: Point3::new(0.0, 0.5, 2.0),
+            direction: Vector3::new(0.0, 0.0, 1.0),
+        };
+        assert!(t.intersection(&r).is_none());
+    }
+
+    #[test]
+    fn it_computes_barycentric_coordinates() {
+        let (u, v) = triangle().barycentric(&Point3::new(0.0, 0.5, 0.0));
+        assert_relative_eq!(u, 0.25);
+        assert_relative_eq!(v, 0.25);
+    }
+
+    #[test]
+    fn it_interpolates_vertex_normals() {
+        let t = smooth_triangle();
+        assert_relative_eq!(
+            t.normal_at(Point3::new(0.0, 1.0, 0.0)),
+            Vector3::new(0.0, 1.0, 0.0)
+        );
+        assert_relative_eq!(
+            t.normal_at(Point3::new(-0.2, 0.3, 0.0)),
+            Vector3::new(-0.5547001962252291, 0.8320502943378437, 0.0)
+        );
+    }
+
+    #[test]
+    fn it_samples_points_inside_itself() {
+        let t = smooth_triangle();
+        assert_relative_eq!(t.area(), 1.0);
+        for &(u, v) in &[(0.0, 0.0), (0.999, 0.5), (0.3, 0.999), (0.5, 0.2)] {
+            let sample = t.sample_surface(u, v).unwrap();
+            let (a, b) = t.triangle.barycentric(&sample.point);
+            assert!(a >= -1e-12 && b >= -1e-12 && a + b <= 1.0 + 1e-12);
+            assert_relative_eq!(sample.point.z, 0.0);
+            assert_relative_eq!(sample.normal, Vector3::new(0.0, 0.0, -1.0));
+            assert_relative_eq!(sample.pdf, 1.0);
+        }
+    }
+}
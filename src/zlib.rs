@@ -0,0 +1,248 @@
+//! Just enough of zlib (RFC 1950) and DEFLATE (RFC 1951) to write compressed image
+//! formats. Data is compressed with LZ77 and the fixed Huffman codes, which is simple and
+//! does well on rendered images without needing to build per-block code tables.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+/// How many earlier positions with the same hash to try before settling on a match.
+const MAX_CHAIN: usize = 64;
+
+/// Base lengths for length codes 257 to 285 and how many extra bits follow each.
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// Base distances for distance codes 0 to 29 and how many extra bits follow each.
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Packs bits least significant first, as DEFLATE requires.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer.to_le_bytes()[0]);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are defined most significant bit first, so they go in reversed.
+    fn write_code(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer.to_le_bytes()[0]);
+        }
+        self.bytes
+    }
+}
+
+fn write_literal_or_length(writer: &mut BitWriter, symbol: u32) {
+    match symbol {
+        0..=143 => writer.write_code(0x30 + symbol, 8),
+        144..=255 => writer.write_code(0x190 + symbol - 144, 9),
+        256..=279 => writer.write_code(symbol - 256, 7),
+        _ => writer.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+// Lengths and distances are at most 258 and 32768, so they and their codes fit in a u32.
+#[allow(clippy::cast_possible_truncation)]
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+    let code = LENGTH_BASES
+        .iter()
+        .rposition(|&base| usize::from(base) <= length)
+        .unwrap_or(0);
+    write_literal_or_length(writer, 257 + code as u32);
+    writer.write(
+        (length - usize::from(LENGTH_BASES[code])) as u32,
+        u32::from(LENGTH_EXTRA_BITS[code]),
+    );
+
+    let code = DISTANCE_BASES
+        .iter()
+        .rposition(|&base| usize::from(base) <= distance)
+        .unwrap_or(0);
+    writer.write_code(code as u32, 5);
+    writer.write(
+        (distance - usize::from(DISTANCE_BASES[code])) as u32,
+        u32::from(DISTANCE_EXTRA_BITS[code]),
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let v = u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2]);
+    (v.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compresses `data` into a single DEFLATE block.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter {
+        bytes: Vec::with_capacity(data.len() / 2),
+        buffer: 0,
+        count: 0,
+    };
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman codes).
+    writer.write(1, 1);
+    writer.write(1, 2);
+
+    // `head` holds the most recent position + 1 for each hash, and `previous` chains each
+    // position to the one before it with the same hash.
+    let mut head = vec![0usize; 1 << HASH_BITS];
+    let mut previous = vec![0usize; data.len()];
+
+    let mut i = 0;
+    while i < data.len() {
+        let (length, distance) = longest_match(data, i, &head, &previous);
+        if length >= MIN_MATCH {
+            write_match(&mut writer, length, distance);
+            for j in i..i + length {
+                insert(data, j, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            write_literal_or_length(&mut writer, u32::from(data[i]));
+            insert(data, i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+
+    write_literal_or_length(&mut writer, 256);
+    writer.finish()
+}
+
+fn insert(data: &[u8], i: usize, head: &mut [usize], previous: &mut [usize]) {
+    if i + MIN_MATCH <= data.len() {
+        let h = hash(&data[i..]);
+        previous[i] = head[h];
+        head[h] = i + 1;
+    }
+}
+
+fn longest_match(data: &[u8], i: usize, head: &[usize], previous: &[usize]) -> (usize, usize) {
+    if i + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_length = MAX_MATCH.min(data.len() - i);
+    let mut best = (0, 0);
+    let mut candidate = head[hash(&data[i..])];
+    for _ in 0..MAX_CHAIN {
+        if candidate == 0 {
+            break;
+        }
+        let start = candidate - 1;
+        if i - start > WINDOW_SIZE {
+            break;
+        }
+        let length = data[start..]
+            .iter()
+            .zip(&data[i..i + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > best.0 {
+            best = (length, i - start);
+            if length == max_length {
+                break;
+            }
+        }
+        candidate = previous[start];
+    }
+    best
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // Sums stay well clear of overflowing within a chunk of this size.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    b << 16 | a
+}
+
+/// Wraps DEFLATE compressed `data` in a zlib stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32K window. FLG: no dictionary, default compression level, with
+    // check bits making CMF * 256 + FLG a multiple of 31.
+    let mut stream = vec![0x78, 0x9c];
+    stream.extend(deflate(data));
+    stream.extend(&adler32(data).to_be_bytes());
+    stream
+}
+
+/// CRC-32 as used by PNG and gzip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_computes_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn it_computes_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+    }
+
+    #[test]
+    fn it_compresses_literals() {
+        // Checked against zlib's inflate.
+        assert_eq!(
+            compress(b"abc"),
+            vec![0x78, 0x9c, 0x4b, 0x4c, 0x4a, 0x06, 0x00, 0x02, 0x4d, 0x01, 0x27]
+        );
+    }
+
+    #[test]
+    fn it_compresses_repeats() {
+        let data = vec![7u8; 1000];
+        let compressed = compress(&data);
+        assert!(compressed.len() < 30);
+        assert_eq!(
+            &compressed[compressed.len() - 4..],
+            &adler32(&data).to_be_bytes()
+        );
+    }
+}